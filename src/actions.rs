@@ -3,6 +3,9 @@ use dotrix::{
     input::{ ActionMapper, Button, KeyCode, Mapper, Modifiers, },
 };
 
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeSet;
+
 pub fn init_actions(input: &mut Input) {
     input.set_mapper(Box::new(Mapper::<Action>::new()));
 
@@ -14,6 +17,12 @@ pub fn init_actions(input: &mut Input) {
         (Action::TurnLeft, Button::Key(KeyCode::Q), Modifiers::empty()),
         (Action::TurnRight, Button::Key(KeyCode::E), Modifiers::empty()),
         (Action::RewindTime, Button::Key(KeyCode::Space), Modifiers::empty()),
+        (Action::SlowTime, Button::Key(KeyCode::LShift), Modifiers::empty()),
+        (Action::RewindFaster, Button::Key(KeyCode::Up), Modifiers::empty()),
+        (Action::RewindSlower, Button::Key(KeyCode::Down), Modifiers::empty()),
+        (Action::ScrubTime, Button::Key(KeyCode::Tab), Modifiers::empty()),
+        (Action::ScrubForward, Button::Key(KeyCode::F), Modifiers::empty()),
+        (Action::SwitchBranch, Button::Key(KeyCode::B), Modifiers::empty()),
         (Action::Pause, Button::Key(KeyCode::Escape), Modifiers::empty()),
         (Action::RotateCamera, Button::MouseRight, Modifiers::empty()),
         (Action::SelectActiveObjectRight, Button::Key(KeyCode::Right), Modifiers::empty()),
@@ -21,7 +30,7 @@ pub fn init_actions(input: &mut Input) {
     ]);
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Serialize, Deserialize)]
 pub enum Action {
     MoveForward,
     MoveBackward,
@@ -30,6 +39,12 @@ pub enum Action {
     TurnLeft,
     TurnRight,
     RewindTime,
+    SlowTime,
+    RewindFaster,
+    RewindSlower,
+    ScrubTime,
+    ScrubForward,
+    SwitchBranch,
     Pause,
     RotateCamera,
     SelectActiveObjectRight,
@@ -37,6 +52,74 @@ pub enum Action {
 
 }
 
+pub const ACTIONS: [Action; 17] = [
+    Action::MoveForward,
+    Action::MoveBackward,
+    Action::MoveLeft,
+    Action::MoveRight,
+    Action::TurnLeft,
+    Action::TurnRight,
+    Action::RewindTime,
+    Action::SlowTime,
+    Action::RewindFaster,
+    Action::RewindSlower,
+    Action::ScrubTime,
+    Action::ScrubForward,
+    Action::SwitchBranch,
+    Action::Pause,
+    Action::RotateCamera,
+    Action::SelectActiveObjectRight,
+    Action::SelectActiveObjectLeft,
+];
+
+/// Input sampled for a single simulation tick
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct TickInput {
+    pub hold: BTreeSet<Action>,
+    pub activated: BTreeSet<Action>,
+    pub camera_pan: f32,
+}
+
+impl TickInput {
+    pub fn is_action_hold(&self, action: Action) -> bool {
+        self.hold.contains(&action)
+    }
+
+    pub fn is_action_activated(&self, action: Action) -> bool {
+        self.activated.contains(&action)
+    }
+}
+
+/// Collects actions activated between ticks, so that none of them are lost or
+/// repeated when a frame runs zero or several ticks
+#[derive(Default)]
+pub struct Sampler {
+    activated: BTreeSet<Action>,
+}
+
+impl Sampler {
+    pub fn collect(&mut self, input: &Input) {
+        for action in ACTIONS.iter() {
+            if input.is_action_activated(*action) {
+                self.activated.insert(*action);
+            }
+        }
+    }
+
+    pub fn sample(&mut self, input: &Input, camera_pan: f32) -> TickInput {
+        let hold = ACTIONS.iter()
+            .filter(|action| input.is_action_hold(**action))
+            .copied()
+            .collect();
+
+        TickInput {
+            hold,
+            activated: std::mem::take(&mut self.activated),
+            camera_pan,
+        }
+    }
+}
+
 // Bind Inputs and Actions
 impl ActionMapper<Action> for Input {
     fn action_mapped(&self, action: Action) -> Option<(Button, Modifiers)> {
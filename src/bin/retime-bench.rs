@@ -0,0 +1,94 @@
+use retime::{
+    actions::TickInput,
+    headless::Simulation,
+    time,
+};
+
+use std::alloc::{ GlobalAlloc, Layout, System, };
+use std::sync::atomic::{ AtomicUsize, Ordering, };
+use std::time::Instant;
+use std::{env, process};
+
+const ITERATIONS: usize = 100;
+
+/// Counts the bytes allocated on the heap to measure the snapshot sizes
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Returns the average bytes allocated and seconds spent by the function
+fn measure<T>(mut f: impl FnMut() -> T) -> (usize, f64) {
+    let allocated = ALLOCATED.load(Ordering::Relaxed);
+    let start = Instant::now();
+
+    for _ in 0..ITERATIONS {
+        std::hint::black_box(f());
+    }
+
+    let seconds = start.elapsed().as_secs_f64() / ITERATIONS as f64;
+    let bytes = (ALLOCATED.load(Ordering::Relaxed) - allocated) / ITERATIONS;
+
+    (bytes, seconds)
+}
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    let level_name = match args.get(0) {
+        Some(level_name) => level_name,
+        None => {
+            println!("Usage: retime-bench <level> [ticks]");
+            process::exit(2);
+        },
+    };
+
+    let ticks = args.get(1).and_then(|ticks| ticks.parse().ok()).unwrap_or(600);
+
+    let mut simulation = match Simulation::load(&[level_name, ".yaml"].join("")) {
+        Ok(simulation) => simulation,
+        Err(e) => {
+            println!("FAILED {}", e);
+            process::exit(1);
+        },
+    };
+
+    let input = TickInput::default();
+    for _ in 0..ticks {
+        if !simulation.tick(&input) {
+            break;
+        }
+    }
+
+    let physics_state = &mut simulation.physics_state;
+    let physics = physics_state.physics.clone().expect("physics::State must be defined");
+
+    // full snapshot of every tick
+    let (full_bytes, full_restore) = measure(|| physics.clone());
+
+    // moving bodies of every tick and a keyframe every KEYFRAME_INTERVAL ticks
+    let (delta_bytes, _) = measure(|| physics_state.frame(false));
+    let delta_bytes = delta_bytes + full_bytes / time::KEYFRAME_INTERVAL;
+
+    let frame = physics_state.frame(false);
+    let (_, delta_restore) = measure(|| physics_state.restore_frame(&frame, &physics));
+
+    println!("{} after {} ticks", level_name, simulation.ticks);
+    println!("full snapshots:  {:>10} bytes/frame, rewind {:>8.1} us/frame",
+        full_bytes, full_restore * 1e6);
+    println!("delta snapshots: {:>10} bytes/frame, rewind {:>8.1} us/frame",
+        delta_bytes, delta_restore * 1e6);
+}
@@ -0,0 +1,64 @@
+use retime::{
+    headless::{ Script, Simulation, },
+    replay::{ self, Replay, },
+};
+
+use std::{env, process};
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+
+    if args.is_empty() {
+        println!("Usage: retime-headless <script.yaml>... | --replay <level>");
+        process::exit(2);
+    }
+
+    let result = if args[0] == "--replay" {
+        match args.get(1) {
+            Some(level_name) => run_replay(level_name),
+            None => Err("--replay expects a level name".to_string()),
+        }
+    } else {
+        args.iter().map(|file_path| run_script(file_path)).collect()
+    };
+
+    if let Err(e) = result {
+        println!("FAILED {}", e);
+        process::exit(1);
+    }
+}
+
+fn run_script(file_path: &str) -> Result<(), String> {
+    let script = Script::from_file(file_path)?;
+    let time = script.run()?;
+    println!("ok {} ({:04.1} secs)", file_path, time);
+    Ok(())
+}
+
+fn run_replay(level_name: &str) -> Result<(), String> {
+    let replay = Replay::load(level_name)
+        .ok_or(format!("{}: replay not found", level_name))?;
+
+    let mut simulation = Simulation::load(&[level_name, ".yaml"].join(""))?;
+
+    for input in replay.ticks.iter() {
+        if !simulation.tick(input) {
+            break;
+        }
+    }
+
+    let position = simulation.player_position()
+        .ok_or(format!("{}: player is not spawned", level_name))?;
+
+    if simulation.ticks != replay.ticks.len() ||
+        replay::checksum(position) != replay.checksum
+    {
+        return Err(format!(
+            "{}: replay desynced, expected {:?} after {} ticks, got {:?} after {}",
+            level_name, replay.final_position, replay.ticks.len(), position, simulation.ticks
+        ));
+    }
+
+    println!("ok {} ({:04.1} secs)", level_name, simulation.time());
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use retime::validate;
+
+use std::{fs, io, path, process};
+
+fn main() {
+    let mut file_names = Vec::new();
+
+    if let Err(e) = level_files(path::Path::new("./levels/"), "", &mut file_names) {
+        println!("Cannot read the levels folder! {}", e);
+        process::exit(2);
+    }
+
+    file_names.sort();
+
+    let mut problems = 0;
+
+    for file_name in file_names.iter() {
+        let level_problems = validate::validate(file_name);
+
+        if level_problems.is_empty() {
+            println!("ok {}", file_name);
+        }
+
+        for problem in level_problems.iter() {
+            println!("error {}", problem);
+        }
+
+        problems += level_problems.len();
+    }
+
+    if problems > 0 {
+        println!("{} problem(s) found", problems);
+        process::exit(1);
+    }
+}
+
+/// Collects the level files of the folder and its subfolders, named relative
+/// to the levels folder
+fn level_files(dir: &path::Path, prefix: &str, file_names: &mut Vec<String>) -> io::Result<()> {
+    for file in fs::read_dir(dir)?.filter_map(|file| file.ok()) {
+        let path = file.path();
+        let name = match file.file_name().to_str() {
+            Some(name) => [prefix, name].join(""),
+            None => continue,
+        };
+
+        if path.is_dir() {
+            level_files(&path, &[&name, "/"].join(""), file_names)?;
+        } else if path.extension().and_then(|ext| ext.to_str()) == Some("yaml") {
+            file_names.push(name);
+        }
+    }
+
+    Ok(())
+}
@@ -1,12 +1,11 @@
 use std::f32::consts::PI;
 
 use dotrix::{
-    Transform, World, Input,
+    Transform, World,
     ecs::{ Mut, Const, Entity, },
     math::{ Vec3, },
 };
 
-use crate::actions::Action;
 use crate::time;
 
 const DY: f32 = 8.0;
@@ -37,7 +36,6 @@ pub fn init (
 pub fn control (
     mut state: Mut<State>,
     world: Const<World>,
-    input: Const<Input>,
     mut camera: Mut<dotrix::Camera>,
 ) {
     // update camera properties
@@ -57,80 +55,25 @@ pub fn control (
         player.0.translate.z
     );
 
-    // select next object
-    let mut query = world.query::<(
-        &mut Transform, &mut time::ActionableObject,
-    )>();
-
-    let mut select_next_active_object = false;
-    let mut selected_found = false;
-    let mut select_player = false;
-    let mut object = query.next().expect("At least one object should be spawned");
-    let mut next_object = None;
-
-    let reversed = input.is_action_activated(Action::SelectActiveObjectLeft);
-
-    let select = reversed ||
-        input.is_action_activated(Action::SelectActiveObjectRight);
-
-    loop {
-        let is_selected = object.1.selected;
-
-        if object.1.selected {
-            selected_found = true;
-            if !object.1.active {
-                select_player = true;
-                object.1.selected = false;
-            } else if select {
-                select_next_active_object = true;
-                object.1.selected = false;
-            }
-        }
-
-        if (!next_object.is_some() || selected_found || reversed) && object.1.active && !is_selected {
-            if selected_found && reversed && next_object.is_some() {
-                break;
-            }
-
-            selected_found = false;
+    // look at the selected object
+    state.position = None;
 
-            next_object = Some(object);
+    let query = world.query::<(&Transform, &time::ActionableObject,)>();
 
-            if selected_found {
-                break;
-            }
-        }
-
-        match query.next() {
-            Some(obj) => object = obj,
-            None => break,
+    for (transform, object) in query {
+        if object.selected && !object.is_player {
+            state.position = Some(transform.translate);
         }
     }
 
-    if select_next_active_object || select_player {
-        if select_player || !next_object.is_some() {
-            next_object = Some(player);
-        }
-
-        if let Some(object) = next_object {
-            if object.1.is_player {
-                state.position = None;
-            } else {
-                state.position = Some(object.0.translate);
-            }
-
-            object.1.selected = true;
-        }
-
-        match state.position {
-            None => {
-                camera.position = None;
-            },
-            Some(mut camera_position) => {
-                camera_position.y += DY;
-                camera_position.z += DZ;
-                camera.position = Some(camera_position);
-            },
-        }
+    match state.position {
+        None => {
+            camera.position = None;
+        },
+        Some(mut camera_position) => {
+            camera_position.y += DY;
+            camera_position.z += DZ;
+            camera.position = Some(camera_position);
+        },
     }
 }
@@ -0,0 +1,143 @@
+use dotrix::{
+    Assets, World, Transform, Color,
+    pbr::{ Model, Material, },
+    math::{ Vec3, },
+    renderer::Render,
+};
+
+use crate::physics::{ self, vector, };
+use crate::player;
+use crate::time;
+
+const SCALE: f32 = 0.5;
+const MIN_DIST: f32 = 2.0;
+
+pub struct State {
+    position: Vec3,
+    /// Run time when the checkpoint was passed, the latest one is used to respawn
+    activated_at: Option<f32>,
+}
+
+impl State {
+    pub fn is_active(&self) -> bool {
+        self.activated_at.is_some()
+    }
+}
+
+impl time::Rewindable for State {
+    type Snapshot = Option<f32>;
+
+    fn snapshot(&self) -> Option<f32> {
+        self.activated_at
+    }
+
+    fn restore(&mut self, activated_at: &Option<f32>) {
+        self.activated_at = *activated_at;
+    }
+}
+
+pub fn spawn(
+    world: &mut World,
+    assets: &mut Assets,
+    physics_state: &mut physics::State,
+    position: Vec3,
+    trigger: Option<physics::TriggerShape>,
+) {
+    let sensor = physics_state.insert_sensor(
+        trigger.unwrap_or(physics::TriggerShape::Ball {
+            radius: player::sensor_radius(MIN_DIST),
+        }),
+        vector![position.x, position.y, position.z],
+    );
+
+    let texture = assets.register("target_flag::texture");
+    let mesh = assets.register("target_flag::mesh");
+
+    world.spawn(Some((
+        Model::from(mesh),
+        Material {
+            texture,
+            albedo: Color::rgb(0.5, 0.5, 0.5),
+            ..Default::default()
+        },
+        Transform {
+            translate: position,
+            scale: Vec3::new(SCALE, SCALE, SCALE),
+            ..Default::default()
+        },
+        State { position, activated_at: None },
+        time::History::<State>::default(),
+        physics::Sensor { collider: sensor },
+        Render::default(),
+    )));
+}
+
+pub fn control(
+    world: &World,
+    physics_state: &physics::State,
+    time: f32,
+) {
+    let player_collider = match player::collider(world) {
+        Some(collider) => collider,
+        None => return,
+    };
+
+    let query = world.query::<(&mut State, &mut Material, &physics::Sensor,)>();
+
+    for (state, material, sensor) in query {
+        if !state.is_active() && physics_state.intersection_started(player_collider, sensor.collider) {
+            state.activated_at = Some(time);
+        }
+
+        material.albedo = if state.is_active() {
+            Color::rgb(0.2, 0.8, 0.2)
+        } else {
+            Color::rgb(0.5, 0.5, 0.5)
+        };
+    }
+}
+
+/// Returns the position of the most recently passed checkpoint
+pub fn last_position(world: &World) -> Option<Vec3> {
+    let query = world.query::<(&State,)>();
+
+    query
+        .filter_map(|(state,)| state.activated_at.map(|activated_at| (activated_at, state.position)))
+        .fold(None, |last: Option<(f32, Vec3)>, (activated_at, position)| match last {
+            Some((last_activated_at, _)) if last_activated_at > activated_at => last,
+            _ => Some((activated_at, position)),
+        })
+        .map(|(_, position)| position)
+}
+
+/// Puts the player at rest above the given position
+pub fn respawn(
+    world: &World,
+    physics_state: &mut physics::State,
+    position: Vec3,
+) {
+    let rigid_body_set = &mut physics_state.physics.as_mut()
+        .expect("physics::State must be defined")
+        .rigid_body_set;
+
+    let query = world.query::<(&physics::RigidBodyHandle, &player::State,)>();
+
+    for (rigid_body, _) in query {
+        let body = rigid_body_set.get_mut(*rigid_body).unwrap();
+
+        body.set_translation(vector![position.x, position.y + 1.0, position.z], true);
+        body.set_linvel(vector![0.0, 0.0, 0.0], true);
+        body.set_angvel(vector![0.0, 0.0, 0.0], true);
+    }
+
+    physics_state.snap_previous_positions();
+}
+
+/// Returns the number of passed and total checkpoints
+pub fn count(world: &World) -> (usize, usize) {
+    let query = world.query::<(&State,)>();
+
+    query.fold((0, 0), |(active, total), (state,)| {
+        (active + state.is_active() as usize, total + 1)
+    })
+}
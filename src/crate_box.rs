@@ -0,0 +1,57 @@
+use dotrix::{
+    Assets, World, Transform,
+    pbr::{ Model, Material, },
+    math::{ Vec3, },
+    renderer::Render,
+};
+
+use crate::physics::{ self, vector, };
+use crate::time;
+
+/// Used when the size is not set in the level file
+pub const DEFAULT_HALF_EXTENTS: (f32, f32, f32) = (1.0, 1.0, 1.0);
+
+pub fn spawn(
+    world: &mut World,
+    assets: &mut Assets,
+    physics_state: &mut physics::State,
+    model: &str,
+    position: Vec3,
+    half_extents: (f32, f32, f32),
+    echo: bool,
+) {
+    let state = physics_state.physics.as_mut().expect("physics::State must be defined");
+
+    let texture = assets.register(&[model, "texture"].join("::"));
+    let mesh = assets.register(&[model, "mesh"].join("::"));
+
+    let rigid_body = physics::RigidBodyBuilder::new_dynamic()
+        .translation(vector![position.x, position.y, position.z])
+        .build();
+    let collider = physics::ColliderBuilder::cuboid(
+        half_extents.0,
+        half_extents.1,
+        half_extents.2,
+    ).build();
+    let body_handle = state.rigid_body_set.insert(rigid_body);
+    state.collider_set.insert_with_parent(
+        collider,
+        body_handle,
+        &mut state.rigid_body_set
+    );
+
+    world.spawn(Some((
+        Model::from(mesh),
+        Material {
+            texture,
+            ..Default::default()
+        },
+        Transform {
+            translate: position,
+            ..Default::default()
+        },
+        Render::default(),
+        time::Echo { enabled: echo },
+        body_handle,
+    )));
+}
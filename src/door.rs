@@ -0,0 +1,145 @@
+use dotrix::{
+    Assets, World, Transform,
+    pbr::{ Model, Material, },
+    math::{ Vec3, },
+    renderer::Render,
+};
+
+use crate::physics::{ self, nalgebra, Isometry, Real, };
+use crate::signal;
+use crate::time;
+
+/// Used when the open time is not set in the level file
+pub const DEFAULT_OPEN_TIME: f32 = 1.0;
+
+pub struct State {
+    closed: Isometry<Real>,
+    open: Isometry<Real>,
+    /// Seconds the door takes to open or close
+    open_time: f32,
+    /// 0.0 is closed, 1.0 is open
+    progress: f32,
+}
+
+impl State {
+    fn pose(&self) -> Isometry<Real> {
+        self.closed.lerp_slerp(&self.open, self.progress)
+    }
+}
+
+impl time::Rewindable for State {
+    type Snapshot = f32;
+
+    fn snapshot(&self) -> f32 {
+        self.progress
+    }
+
+    fn restore(&mut self, progress: &f32) {
+        self.progress = *progress;
+    }
+}
+
+/// Builds a pose from a position and Euler angles in degrees
+pub fn isometry(position: Vec3, rotation: Vec3) -> Isometry<Real> {
+    Isometry::from_parts(
+        nalgebra::Translation3::new(position.x, position.y, position.z),
+        nalgebra::UnitQuaternion::from_euler_angles(
+            rotation.x.to_radians(),
+            rotation.y.to_radians(),
+            rotation.z.to_radians(),
+        ),
+    )
+}
+
+/// Doors use level models, see `level::load_model`
+pub fn spawn(
+    world: &mut World,
+    assets: &mut Assets,
+    physics_state: &mut physics::State,
+    model: &str,
+    closed: Isometry<Real>,
+    open: Isometry<Real>,
+    open_time: f32,
+    receiver: signal::Receiver,
+) {
+    let state = physics_state.physics.as_mut().expect("physics::State must be defined");
+
+    let texture = assets.register(&[model, "texture"].join("::"));
+    let mesh_id = assets.register(&[model, "mesh"].join("::"));
+
+    let rigid_body = physics::RigidBodyBuilder::new_kinematic_position_based()
+        .position(closed)
+        .build();
+    let body_handle = state.rigid_body_set.insert(rigid_body);
+
+    // add door to the collider set
+    let mesh = assets.get(mesh_id).unwrap();
+
+    let collider = physics::trimesh_collider(mesh).build();
+
+    state.collider_set.insert_with_parent(
+        collider,
+        body_handle,
+        &mut state.rigid_body_set
+    );
+
+    world.spawn(Some((
+        Model::from(mesh_id),
+        Material {
+            texture,
+            ..Default::default()
+        },
+        Transform {
+            translate: Vec3::new(
+                closed.translation.x,
+                closed.translation.y,
+                closed.translation.z,
+            ),
+            ..Default::default()
+        },
+        Render::default(),
+        State {
+            closed,
+            open,
+            open_time,
+            progress: 0.0,
+        },
+        receiver,
+        time::History::<State>::default(),
+        time::History::<signal::Receiver>::default(),
+        body_handle,
+    )));
+}
+
+/// Opens the doors while they receive a signal and closes them otherwise
+pub fn control(
+    world: &World,
+    physics_state: &mut physics::State,
+) {
+    let dt = physics_state.dt();
+    let physics = physics_state.physics
+        .as_mut().expect("physics::State must be defined");
+
+    let rigid_body_set = &mut physics.rigid_body_set;
+
+    let query = world.query::<(&physics::RigidBodyHandle, &mut State, &signal::Receiver,)>();
+
+    for (rigid_body, state, receiver) in query {
+        let step = if state.open_time > 0.0 {
+            dt / state.open_time
+        } else {
+            1.0
+        };
+
+        state.progress = if receiver.is_signaled() {
+            (state.progress + step).min(1.0)
+        } else {
+            (state.progress - step).max(0.0)
+        };
+
+        let body = rigid_body_set.get_mut(*rigid_body).unwrap();
+
+        body.set_next_kinematic_position(state.pose());
+    }
+}
+
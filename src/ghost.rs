@@ -0,0 +1,219 @@
+use dotrix::{
+    Assets, World, Transform, Color,
+    pbr::{ Model, Material, },
+    ecs::{ Mut, Const, Entity, },
+    math::{ Vec3, Quat, },
+    renderer::Render,
+};
+
+use crate::level;
+use crate::physics;
+use crate::player;
+use crate::records;
+use crate::replay;
+use crate::states;
+use crate::ui;
+
+use serde::{Serialize, Deserialize};
+use std::{fs, path};
+
+const GHOST_FOLDER: &str = "ghosts";
+
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct Pose {
+    pub position: (f32, f32, f32),
+    pub rotation: (f32, f32, f32, f32),
+}
+
+impl Pose {
+    fn from_isometry(isometry: &physics::Isometry<physics::Real>) -> Self {
+        let rotation = isometry.rotation.into_inner();
+        Self {
+            position: (
+                isometry.translation.x,
+                isometry.translation.y,
+                isometry.translation.z,
+            ),
+            rotation: (
+                rotation.w,
+                rotation.i,
+                rotation.j,
+                rotation.k,
+            ),
+        }
+    }
+}
+
+/// Player poses of a completed run, one per simulation tick
+#[derive(Serialize, Deserialize, Clone, std::fmt::Debug)]
+pub struct Run {
+    pub time: f32,
+    pub poses: Vec<Pose>,
+}
+
+impl Run {
+    fn file_path(level_name: &str) -> path::PathBuf {
+        records::data_dir().join(GHOST_FOLDER).join([level_name, ".yaml"].join(""))
+    }
+
+    pub fn load(level_name: &str) -> Option<Self> {
+        let s = fs::read_to_string(Self::file_path(level_name)).ok()?;
+        serde_yaml::from_str(&s).ok()
+    }
+
+    pub fn save(&self, level_name: &str) {
+        let result = fs::create_dir_all(records::data_dir().join(GHOST_FOLDER))
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_yaml::to_string(self).map_err(|e| e.to_string()))
+            .and_then(|s| fs::write(Self::file_path(level_name), s).map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            println!("Cannot save the ghost run! {}", e);
+        }
+    }
+}
+
+pub struct State {
+    level_name: String,
+    best: Option<Run>,
+    current: Vec<Pose>,
+    /// Ticks the current run is rewound by, the rewound poses are dropped
+    /// once the rewind is finished
+    rewound: usize,
+    entity: Option<Entity>,
+    saved: bool,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            level_name: String::new(),
+            best: None,
+            current: Vec::new(),
+            rewound: 0,
+            entity: None,
+            saved: false,
+        }
+    }
+}
+
+impl State {
+    /// Number of poses of the current run up to the rewound tick
+    fn poses_len(&self) -> usize {
+        self.current.len().saturating_sub(self.rewound)
+    }
+}
+
+pub fn init(
+    level_opt: Const<Option<level::Level>>,
+    mut world: Mut<World>,
+    mut assets: Mut<Assets>,
+    mut state: Mut<State>,
+) {
+    let level = level_opt.as_ref().expect("Some level should be loaded");
+
+    *state = State {
+        level_name: level.name.clone(),
+        best: Run::load(&level.name),
+        ..Default::default()
+    };
+
+    if state.best.is_some() {
+        let texture = assets.register("player::texture");
+        let mesh = assets.register("player::mesh");
+
+        state.entity = Some(world.spawn(Some((
+            Model::from(mesh),
+            Material {
+                texture,
+                albedo: Color::rgba(0.6, 0.8, 1.0, 0.4),
+                ..Default::default()
+            },
+            Transform::default(),
+            Render::default(),
+        ))).first());
+    }
+}
+
+pub fn record(
+    world: &World,
+    physics_state: &physics::State,
+    state: &mut State,
+) {
+    if let Some(player_pose) = player::pose(world, physics_state) {
+        state.current.push(Pose::from_isometry(&player_pose));
+    }
+}
+
+/// Sets the ticks the current run is rewound by
+pub fn rewind(
+    state: &mut State,
+    ticks: usize,
+    finished: bool,
+) {
+    state.rewound = ticks;
+
+    if finished {
+        let len = state.poses_len();
+        state.current.truncate(len);
+        state.rewound = 0;
+    }
+}
+
+pub fn save(
+    stats_opt: Const<Option<states::Stats>>,
+    mut state: Mut<State>,
+    replay_state: Const<replay::State>,
+) {
+    let stats = stats_opt.as_ref()
+        .expect("Game stats should be initialized");
+
+    // the ghost of a watched replay is not saved over the best run
+    if !stats.level_passed || state.saved || replay_state.is_playing() {
+        return;
+    }
+
+    state.saved = true;
+
+    let is_best = match &state.best {
+        Some(best) => stats.time < best.time,
+        None => true,
+    };
+
+    if is_best {
+        let run = Run {
+            time: stats.time,
+            poses: state.current[..state.poses_len()].to_vec(),
+        };
+        run.save(&state.level_name);
+    }
+}
+
+pub fn update(
+    world: Const<World>,
+    state: Const<State>,
+    settings: Const<ui::State>,
+) {
+    let (entity, best) = match (state.entity, &state.best) {
+        (Some(entity), Some(best)) => (entity, best),
+        _ => return,
+    };
+
+    let (transform,) = world.get::<(&mut Transform,)>(entity)
+        .expect("Ghost should be spawned");
+
+    // hold the final pose once the best run is over
+    let pose = match best.poses.get(state.poses_len().min(best.poses.len().max(1) - 1)) {
+        Some(pose) => pose,
+        None => return,
+    };
+
+    transform.translate = Vec3::new(pose.position.0, pose.position.1, pose.position.2);
+    transform.rotate = Quat::new(pose.rotation.0, pose.rotation.1, pose.rotation.2, pose.rotation.3);
+
+    transform.scale = if settings.show_ghost {
+        Vec3::new(1.0, 1.0, 1.0)
+    } else {
+        Vec3::new(0.0, 0.0, 0.0)
+    };
+}
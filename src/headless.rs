@@ -0,0 +1,240 @@
+use dotrix::{
+    Assets, World,
+    assets::Mesh,
+    State as StateStack,
+};
+
+use crate::actions::{ Action, TickInput, };
+use crate::camera;
+use crate::ghost;
+use crate::level;
+use crate::physics;
+use crate::player;
+use crate::simulation;
+use crate::states;
+use crate::time;
+
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeSet;
+use std::{fs, path};
+
+/// Game state advanced without a window or a renderer
+pub struct Simulation {
+    pub world: World,
+    pub physics_state: physics::State,
+    pub time_stack: time::Stack,
+    pub stats: Option<states::Stats>,
+    pub ghost_state: ghost::State,
+    pub state_stack: StateStack,
+    pub ticks: usize,
+}
+
+impl Simulation {
+    pub fn load(file_name: &str) -> Result<Self, String> {
+        let level = level::Level::from_file(file_name).map_err(|e| e.to_string())?;
+
+        let mut assets = Assets::default();
+        let level_mesh = [level.model(), "mesh"].join("::");
+        store_mesh(
+            &mut assets,
+            &["assets/levels/", level.model(), ".gltf"].join(""),
+            &level_mesh,
+        )?;
+        store_mesh(&mut assets, "assets/trampoline.gltf", "trampoline::mesh")?;
+
+        for object in level.objects().iter() {
+            let model = match object {
+                level::Objects::MovingPlatform(init_state) => &init_state.model,
+                level::Objects::Door(init_state) => &init_state.model,
+                level::Objects::Crate(init_state) => &init_state.model,
+                _ => continue,
+            };
+
+            store_mesh(
+                &mut assets,
+                &["assets/levels/", model, ".gltf"].join(""),
+                &[model, "mesh"].join("::"),
+            )?;
+        }
+
+        let stats = states::Stats::new(&level);
+        let time_stack = level.time_stack();
+
+        let mut simulation = Self {
+            world: World::default(),
+            physics_state: physics::State::default(),
+            time_stack,
+            stats: Some(stats),
+            ghost_state: ghost::State::default(),
+            state_stack: StateStack::default(),
+            ticks: 0,
+        };
+
+        level.spawn(
+            &mut simulation.world,
+            &mut assets,
+            &mut simulation.physics_state,
+            &mut camera::State::default(),
+        );
+
+        simulation.state_stack.push(states::RunLevel {});
+
+        Ok(simulation)
+    }
+
+    /// Advances one tick, returns false once the level is no longer running
+    pub fn tick(&mut self, input: &TickInput) -> bool {
+        if !simulation::is_running(&self.state_stack) {
+            return false;
+        }
+
+        simulation::tick(
+            &self.world,
+            input,
+            &mut self.physics_state,
+            &mut self.time_stack,
+            &mut self.stats,
+            &mut self.ghost_state,
+            &mut self.state_stack,
+        );
+        self.ticks += 1;
+
+        true
+    }
+
+    pub fn level_passed(&self) -> bool {
+        self.stats.as_ref().map(|stats| stats.level_passed).unwrap_or(false)
+    }
+
+    pub fn time(&self) -> f32 {
+        self.stats.as_ref().map(|stats| stats.time).unwrap_or(0.0)
+    }
+
+    pub fn player_position(&self) -> Option<(f32, f32, f32)> {
+        player::pose(&self.world, &self.physics_state)
+            .map(|pose| (pose.translation.x, pose.translation.y, pose.translation.z))
+    }
+}
+
+/// Reads the first mesh of a glTF file into the assets under the given name
+fn store_mesh(assets: &mut Assets, file_path: &str, name: &str) -> Result<(), String> {
+    let (vertices, indices) = read_mesh(file_path)?;
+
+    let mut mesh = Mesh::default();
+    mesh.with_vertices(&vertices);
+    mesh.with_indices(&indices);
+    assets.store_as(mesh, name);
+
+    Ok(())
+}
+
+/// Reads vertex positions and indices of the first mesh of a glTF file
+pub fn read_mesh(file_path: &str) -> Result<(Vec<[f32; 3]>, Vec<u32>), String> {
+    let (document, buffers, _) = gltf::import(file_path)
+        .map_err(|e| format!("{}: {}", file_path, e))?;
+
+    let primitive = document.meshes()
+        .flat_map(|mesh| mesh.primitives())
+        .next()
+        .ok_or(format!("{}: no meshes found", file_path))?;
+
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+
+    let vertices = reader.read_positions()
+        .ok_or(format!("{}: mesh has no positions", file_path))?
+        .collect::<Vec<[f32; 3]>>();
+
+    let indices = reader.read_indices()
+        .ok_or(format!("{}: mesh has no indices", file_path))?
+        .into_u32()
+        .collect::<Vec<u32>>();
+
+    Ok((vertices, indices))
+}
+
+/// Inputs held for a number of ticks
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct Step {
+    pub ticks: usize,
+    #[serde(default)]
+    pub hold: Vec<Action>,
+    #[serde(default)]
+    pub activate: Vec<Action>,
+    #[serde(default)]
+    pub camera_pan: f32,
+}
+
+/// Scripted run of a level with the expected outcome
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct Script {
+    pub level: String,
+    pub steps: Vec<Step>,
+    pub expect_passed: bool,
+    #[serde(default)]
+    pub expect_max_time: Option<f32>,
+}
+
+impl Script {
+    pub fn from_file(file_path: &str) -> Result<Self, String> {
+        let s = fs::read_to_string(path::Path::new(file_path))
+            .map_err(|e| format!("{}: {}", file_path, e))?;
+        serde_yaml::from_str(&s).map_err(|e| format!("{}: {}", file_path, e))
+    }
+
+    /// Per-tick inputs of the script, actions are activated on the first tick of a step
+    pub fn inputs(&self) -> Vec<TickInput> {
+        let mut inputs = Vec::new();
+
+        for step in self.steps.iter() {
+            let hold = step.hold.iter().chain(step.activate.iter())
+                .copied()
+                .collect::<BTreeSet<_>>();
+
+            for tick in 0..step.ticks {
+                inputs.push(TickInput {
+                    hold: hold.clone(),
+                    activated: if tick == 0 {
+                        step.activate.iter().copied().collect()
+                    } else {
+                        BTreeSet::new()
+                    },
+                    camera_pan: step.camera_pan,
+                });
+            }
+        }
+
+        inputs
+    }
+
+    /// Runs the script and returns an error describing a failed expectation
+    pub fn run(&self) -> Result<f32, String> {
+        let mut simulation = Simulation::load(&[&self.level, ".yaml"].join(""))?;
+
+        for input in self.inputs().iter() {
+            if !simulation.tick(input) {
+                break;
+            }
+        }
+
+        let passed = simulation.level_passed();
+        let time = simulation.time();
+
+        if passed != self.expect_passed {
+            return Err(format!(
+                "{}: expected level passed = {}, got {} after {} ticks",
+                self.level, self.expect_passed, passed, simulation.ticks
+            ));
+        }
+
+        if let Some(max_time) = self.expect_max_time {
+            if passed && time > max_time {
+                return Err(format!(
+                    "{}: passed in {:.2} secs, expected at most {:.2}",
+                    self.level, time, max_time
+                ));
+            }
+        }
+
+        Ok(time)
+    }
+}
@@ -1,5 +1,5 @@
 use dotrix::{
-    Assets, World, Id, Frame, Transform,
+    Assets, World, Id, Frame, Transform, Color,
     assets::Mesh,
     pbr::{ self, Model, Material, },
     ecs::{ Mut, Const, Context,},
@@ -8,15 +8,24 @@ use dotrix::{
     State as StateStack,
 };
 
+use crate::checkpoint;
+use crate::crate_box;
+use crate::door;
+use crate::light;
 use crate::physics;
+use crate::past_self;
+use crate::platform;
+use crate::signal;
+use crate::simulation;
 use crate::time_capsule;
 use crate::player;
 use crate::states;
+use crate::time;
 use crate::trampoline;
 use crate::camera;
 
 use serde::{Serialize, Deserialize};
-use std::{fs, path};
+use std::{fmt, fs, path};
 
 pub struct Ctx {
     loaded: Vec<String>,
@@ -32,9 +41,65 @@ impl Default for Ctx {
     }
 }
 
+/// Problem found while reading a level file
+#[derive(Clone, std::fmt::Debug)]
+pub struct LevelError {
+    pub file_name: String,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl LevelError {
+    pub fn new(file_name: &str, message: String) -> Self {
+        Self {
+            file_name: file_name.to_string(),
+            line: None,
+            column: None,
+            message,
+        }
+    }
+
+    fn from_yaml(file_name: &str, error: serde_yaml::Error) -> Self {
+        let location = error.location();
+        Self {
+            file_name: file_name.to_string(),
+            line: location.as_ref().map(|location| location.line()),
+            column: location.as_ref().map(|location| location.column()),
+            message: error.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(
+                f, "{}:{}:{}: {}", self.file_name, line, column, self.message
+            ),
+            _ => write!(f, "{}: {}", self.file_name, self.message),
+        }
+    }
+}
+
+impl std::error::Error for LevelError {}
+
 #[derive(Serialize, Deserialize, std::fmt::Debug)]
 pub struct TimeCapsuleInit {
     pub position: (f32, f32, f32),
+    /// Rewind time added to the hourglass in seconds
+    #[serde(default)]
+    pub amount: Option<f32>,
+    /// Seconds until the capsule appears again after it was collected
+    #[serde(default)]
+    pub respawn_delay: Option<f32>,
+    #[serde(default)]
+    pub overflow: time_capsule::Overflow,
+    /// Model in the assets folder
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub color: Option<(f32, f32, f32)>,
 }
 
 #[derive(Serialize, Deserialize, std::fmt::Debug)]
@@ -44,8 +109,123 @@ pub struct PlayerInit {
 
 #[derive(Serialize, Deserialize, std::fmt::Debug)]
 pub struct TrampolineInit {
+    /// Id used by switches to arm the trampoline
+    #[serde(default)]
+    pub id: Option<String>,
     pub base_position: (f32, f32, f32),
-    pub button_position: (f32, f32, f32),
+    #[serde(default)]
+    pub button_position: Option<(f32, f32, f32)>,
+    /// Additional activation buttons, any of them arms the trampoline
+    #[serde(default)]
+    pub buttons: Vec<(f32, f32, f32)>,
+    #[serde(default)]
+    pub impulse: Option<(f32, f32, f32)>,
+    #[serde(default)]
+    pub radius: Option<f32>,
+    #[serde(default)]
+    pub button_radius: Option<f32>,
+    /// Launches per activation
+    #[serde(default)]
+    pub charges: Option<u32>,
+    /// Seconds until the trampoline re-arms itself after its last launch
+    #[serde(default)]
+    pub cooldown: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct CheckpointInit {
+    pub position: (f32, f32, f32),
+    #[serde(default)]
+    pub trigger: Option<physics::TriggerShape>,
+}
+
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct MovingPlatformInit {
+    /// Id used by switches to send signals to the platform
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Model in the assets/levels folder
+    pub model: String,
+    pub waypoints: Vec<(f32, f32, f32)>,
+    /// Speed per segment, the last one is used for the rest of the segments
+    #[serde(default)]
+    pub speeds: Vec<f32>,
+    /// Time to wait at every waypoint in seconds
+    #[serde(default)]
+    pub wait_times: Vec<f32>,
+    /// The platform only moves while a switch targeting it is on
+    #[serde(default)]
+    pub wait_for_signal: bool,
+    /// The platform is immune to time: it keeps its position and state when
+    /// the time is rewound, it stands still during the rewind
+    #[serde(default)]
+    pub echo: bool,
+}
+
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct CrateInit {
+    /// Model in the assets/levels folder
+    pub model: String,
+    pub position: (f32, f32, f32),
+    #[serde(default)]
+    pub half_extents: Option<(f32, f32, f32)>,
+    /// The crate keeps its position while the time is rewound
+    #[serde(default)]
+    pub echo: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug)]
+pub struct DoorPose {
+    pub position: (f32, f32, f32),
+    /// Euler angles in degrees
+    #[serde(default)]
+    pub rotation: (f32, f32, f32),
+}
+
+impl DoorPose {
+    fn isometry(&self) -> physics::Isometry<physics::Real> {
+        door::isometry(
+            Vec3::new(self.position.0, self.position.1, self.position.2),
+            Vec3::new(self.rotation.0, self.rotation.1, self.rotation.2),
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct DoorInit {
+    /// Id used by switches and keys to open the door
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Model in the assets/levels folder
+    pub model: String,
+    pub closed: DoorPose,
+    pub open: DoorPose,
+    /// Seconds to open or close the door
+    #[serde(default)]
+    pub open_time: Option<f32>,
+}
+
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct SwitchInit {
+    pub position: (f32, f32, f32),
+    pub kind: signal::SwitchKind,
+    /// Ids of the objects receiving the signal
+    #[serde(default)]
+    pub targets: Vec<String>,
+    #[serde(default)]
+    pub trigger: Option<physics::TriggerShape>,
+}
+
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct LightInit {
+    /// Id used by switches to turn the light on
+    #[serde(default)]
+    pub id: Option<String>,
+    pub position: (f32, f32, f32),
+    #[serde(default)]
+    pub color: Option<(f32, f32, f32)>,
+    #[serde(default)]
+    pub intensity: Option<f32>,
 }
 
 #[derive(Serialize, Deserialize, std::fmt::Debug)]
@@ -53,22 +233,338 @@ pub enum Objects {
     TimeCapsule(TimeCapsuleInit),
     Player(PlayerInit),
     Trampoline(TrampolineInit),
+    Checkpoint(CheckpointInit),
+    MovingPlatform(MovingPlatformInit),
+    Switch(SwitchInit),
+    Door(DoorInit),
+    Crate(CrateInit),
+    Light(LightInit),
+}
+
+impl Objects {
+    /// Returns the id other objects can refer to this object by
+    pub fn id(&self) -> Option<&str> {
+        match self {
+            Objects::Trampoline(init_state) => init_state.id.as_deref(),
+            Objects::MovingPlatform(init_state) => init_state.id.as_deref(),
+            Objects::Door(init_state) => init_state.id.as_deref(),
+            Objects::Light(init_state) => init_state.id.as_deref(),
+            _ => None,
+        }
+    }
 }
 
+/// Distance from the target position the level is passed at
+const TARGET_MIN_DIST: f32 = 2.0;
+
+/// Marks the target flag entity
+pub struct Target {}
+
 #[derive(Serialize, Deserialize, std::fmt::Debug)]
 pub struct Level {
+    #[serde(skip)]
+    pub name: String,
     model: String,
     pub target_position: (f32, f32, f32),
+    /// Sensor shape around the target position, a ball by default
+    #[serde(default)]
+    pub target_trigger: Option<physics::TriggerShape>,
+    /// The player is respawned at the last checkpoint when falling below this height
+    #[serde(default)]
+    pub kill_height: Option<f32>,
+    /// Longest period that can be rewound in seconds
+    #[serde(default)]
+    pub rewind_window: Option<f32>,
+    /// Rewind time available at the start in seconds
+    #[serde(default)]
+    pub rewind_budget: Option<f32>,
+    #[serde(default)]
+    pub slow_time: time::SlowTime,
+    /// Actions discarded by changing the past are replayed by a second ball
+    #[serde(default)]
+    pub past_self: bool,
     objects: Vec<Objects>,
 }
 
 impl Level {
-    pub fn from_file(file_name: &str) -> Self {
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn objects(&self) -> &[Objects] {
+        &self.objects
+    }
+
+    pub fn target_trigger(&self) -> physics::TriggerShape {
+        self.target_trigger.unwrap_or(physics::TriggerShape::Ball {
+            radius: player::sensor_radius(TARGET_MIN_DIST),
+        })
+    }
+
+    /// Time stack sized by the rewind window of the level
+    pub fn time_stack(&self) -> time::Stack {
+        let to_ticks = |secs: f32| (secs * simulation::TICK_RATE).round().max(0.0) as usize;
+
+        time::Stack::new(
+            self.rewind_window.map(to_ticks).unwrap_or(time::DEFAULT_STACK_SIZE),
+            self.rewind_budget.map(to_ticks).unwrap_or(0),
+        )
+    }
+
+    pub fn from_file(file_name: &str) -> Result<Self, LevelError> {
         let s = fs::read_to_string(
             path::Path::new(".").join("levels").join(file_name)
-        ).unwrap();
-        serde_yaml::from_str(&s).unwrap()
+        ).map_err(|e| LevelError::new(file_name, e.to_string()))?;
+        let mut level: Self = serde_yaml::from_str(&s)
+            .map_err(|e| LevelError::from_yaml(file_name, e))?;
+        level.name = path::Path::new(file_name).file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or(file_name)
+            .to_string();
+        Ok(level)
     }
+
+    /// Spawns the level entities and colliders; does not require a renderer,
+    /// only the level meshes stored in the assets
+    pub fn spawn(
+        mut self,
+        world: &mut World,
+        assets: &mut Assets,
+        physics_state: &mut physics::State,
+        camera_state: &mut camera::State,
+    ) {
+        // spawn the flag
+        let texture = assets.register("target_flag::texture");
+        let mesh = assets.register("target_flag::mesh");
+
+        println!("{:?} {:?}", mesh, texture);
+
+        let target_sensor = physics_state.insert_sensor(
+            self.target_trigger(),
+            physics::vector![
+                self.target_position.0,
+                self.target_position.1,
+                self.target_position.2
+            ],
+        );
+
+        world.spawn(Some((
+            Model::from(mesh),
+            Material {
+                texture,
+                ..Default::default()
+            },
+            Transform {
+                translate: Vec3::new(
+                    self.target_position.0,
+                    self.target_position.1,
+                    self.target_position.2
+                ),
+                ..Default::default()
+            },
+            Render::default(),
+            Target {},
+            physics::Sensor { collider: target_sensor },
+        )));
+
+        // spawn level model
+        let texture = assets.register(
+            &[&*self.model, "texture"].join("::")
+        );
+        let mesh_id = assets.register(
+            &[&*self.model, "mesh"].join("::")
+        );
+
+        world.spawn(
+            (pbr::solid::Entity {
+                mesh: mesh_id,
+                texture,
+                ..Default::default()
+            }).some()
+        );
+
+        // add the terrain to the collider set
+        let mesh = assets.get(mesh_id).unwrap();
+
+        let collider = physics::trimesh_collider(mesh).build();
+
+        physics_state.physics.as_mut().expect("physics::State must be defined")
+            .collider_set.insert(collider);
+
+        while let Some(object) = self.objects.pop() {
+            match object {
+                Objects::Player(init_state) => {
+                    if camera_state.player_entity.is_some() {
+                        println!("Only one player entity is allowed");
+                    } else {
+                        camera_state.player_entity = Some(player::spawn(
+                            world,
+                            assets,
+                            physics_state,
+                            Vec3::new(
+                                init_state.position.0,
+                                init_state.position.1,
+                                init_state.position.2
+                            )
+                        ));
+                    }
+                },
+                Objects::TimeCapsule(init_state) => {
+                    let defaults = time_capsule::Config::default();
+
+                    time_capsule::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        Vec3::new(
+                            init_state.position.0,
+                            init_state.position.1,
+                            init_state.position.2
+                        ),
+                        time_capsule::Config {
+                            amount: init_state.amount
+                                .map(|amount| (amount * simulation::TICK_RATE).round() as usize),
+                            respawn_delay: init_state.respawn_delay,
+                            overflow: init_state.overflow,
+                            model: init_state.model.unwrap_or(defaults.model),
+                            color: init_state.color
+                                .map(|color| Color::rgb(color.0, color.1, color.2)),
+                        },
+                    )
+                },
+                Objects::Trampoline(init_state) => {
+                    let defaults = trampoline::Config::default();
+                    let impulse = init_state.impulse.unwrap_or(trampoline::IMPULSE);
+
+                    trampoline::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        Vec3::new(
+                            init_state.base_position.0,
+                            init_state.base_position.1,
+                            init_state.base_position.2
+                        ),
+                        init_state.button_position.iter()
+                            .chain(init_state.buttons.iter())
+                            .map(|button| Vec3::new(button.0, button.1, button.2))
+                            .collect(),
+                        trampoline::Config {
+                            impulse: Vec3::new(impulse.0, impulse.1, impulse.2),
+                            radius: init_state.radius.unwrap_or(defaults.radius),
+                            button_radius: init_state.button_radius
+                                .unwrap_or(defaults.button_radius),
+                            charges: init_state.charges.unwrap_or(defaults.charges),
+                            cooldown: init_state.cooldown,
+                        },
+                        signal::Receiver::new(init_state.id),
+                    )
+                },
+                Objects::MovingPlatform(init_state) => {
+                    platform::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        &init_state.model,
+                        platform::Path::new(
+                            init_state.waypoints.iter()
+                                .map(|waypoint| Vec3::new(waypoint.0, waypoint.1, waypoint.2))
+                                .collect(),
+                            init_state.speeds,
+                            init_state.wait_times,
+                        ),
+                        signal::Receiver::new(init_state.id),
+                        init_state.wait_for_signal,
+                        init_state.echo,
+                    )
+                },
+                Objects::Checkpoint(init_state) => {
+                    checkpoint::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        Vec3::new(
+                            init_state.position.0,
+                            init_state.position.1,
+                            init_state.position.2
+                        ),
+                        init_state.trigger,
+                    )
+                },
+                Objects::Crate(init_state) => {
+                    crate_box::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        &init_state.model,
+                        Vec3::new(
+                            init_state.position.0,
+                            init_state.position.1,
+                            init_state.position.2
+                        ),
+                        init_state.half_extents.unwrap_or(crate_box::DEFAULT_HALF_EXTENTS),
+                        init_state.echo,
+                    )
+                },
+                Objects::Door(init_state) => {
+                    door::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        &init_state.model,
+                        init_state.closed.isometry(),
+                        init_state.open.isometry(),
+                        init_state.open_time.unwrap_or(door::DEFAULT_OPEN_TIME),
+                        signal::Receiver::new(init_state.id),
+                    )
+                },
+                Objects::Switch(init_state) => {
+                    signal::spawn(
+                        world,
+                        assets,
+                        physics_state,
+                        Vec3::new(
+                            init_state.position.0,
+                            init_state.position.1,
+                            init_state.position.2
+                        ),
+                        init_state.kind,
+                        init_state.targets,
+                        init_state.trigger,
+                    )
+                },
+                Objects::Light(init_state) => {
+                    light::spawn(
+                        world,
+                        Vec3::new(
+                            init_state.position.0,
+                            init_state.position.1,
+                            init_state.position.2
+                        ),
+                        init_state.color
+                            .map(|color| Color::rgb(color.0, color.1, color.2))
+                            .unwrap_or(Color::white()),
+                        init_state.intensity.unwrap_or(light::DEFAULT_INTENSITY),
+                        signal::Receiver::new(init_state.id),
+                    )
+                },
+            }
+        }
+
+        if self.past_self {
+            past_self::spawn(world, assets, physics_state);
+        }
+    }
+}
+
+/// Imports a model from the assets/levels folder, used by the level and its
+/// platforms, doors and crates
+pub fn load_model(
+    assets: &mut Assets,
+    model: &str,
+) -> Id<Mesh> {
+    assets.import(&["assets/levels/", model, ".gltf"].join(""));
+    assets.register(&[model, "mesh"].join("::"))
 }
 
 pub fn load_assets(
@@ -84,14 +580,9 @@ pub fn load_assets(
     if !load_state.imported {
         let level = level_opt.as_ref().expect("Some level should be loaded");
 
-        let level_path = ["assets/levels/", &level.model, ".gltf"].join("");
-
         if !ctx.loaded.contains(&level.model) {
-            assets.import(&level_path);
             ctx.loaded.push(level.model.clone());
-            ctx.mesh_ids.push(assets.register(
-                &[&level.model, "mesh"].join("::")
-            ));
+            ctx.mesh_ids.push(load_model(&mut assets, &level.model));
         }
 
         if !ctx.loaded.contains(&"target_flag".to_string()) {
@@ -102,10 +593,12 @@ pub fn load_assets(
 
         for object in level.objects.iter() {
             match object {
-                Objects::TimeCapsule(_) => {
-                    if !ctx.loaded.contains(&"time_capsule".to_string()) {
-                        ctx.mesh_ids.push(time_capsule::load_assets(&mut assets));
-                        ctx.loaded.push("time_capsule".to_string());
+                Objects::TimeCapsule(init_state) => {
+                    let model = init_state.model.as_deref()
+                        .unwrap_or(time_capsule::DEFAULT_MODEL);
+                    if !ctx.loaded.contains(&model.to_string()) {
+                        ctx.mesh_ids.push(time_capsule::load_assets(&mut assets, model));
+                        ctx.loaded.push(model.to_string());
                     }
                 },
                 Objects::Player(_) => {
@@ -114,12 +607,35 @@ pub fn load_assets(
                         ctx.loaded.push("player".to_string());
                     }
                 },
-                Objects::Trampoline(_) => {
+                Objects::Crate(CrateInit { model, .. }) |
+                Objects::Door(DoorInit { model, .. }) => {
+                    if !ctx.loaded.contains(model) {
+                        ctx.mesh_ids.push(load_model(&mut assets, model));
+                        ctx.loaded.push(model.clone());
+                    }
+                },
+                Objects::Switch(init_state) if matches!(init_state.kind, signal::SwitchKind::Key) => {
+                    let model = time_capsule::DEFAULT_MODEL;
+                    if !ctx.loaded.contains(&model.to_string()) {
+                        ctx.mesh_ids.push(time_capsule::load_assets(&mut assets, model));
+                        ctx.loaded.push(model.to_string());
+                    }
+                },
+                // switches reuse the trampoline button model
+                Objects::Trampoline(_) | Objects::Switch(_) => {
                     if !ctx.loaded.contains(&"trampoline".to_string()) {
                         ctx.mesh_ids.push(trampoline::load_assets(&mut assets));
                         ctx.loaded.push("trampoline".to_string());
                     }
                 },
+                Objects::MovingPlatform(init_state) => {
+                    if !ctx.loaded.contains(&init_state.model) {
+                        ctx.mesh_ids.push(load_model(&mut assets, &init_state.model));
+                        ctx.loaded.push(init_state.model.clone());
+                    }
+                },
+                // checkpoints reuse the target flag model, lights have none
+                Objects::Checkpoint(_) | Objects::Light(_) => {},
             }
         }
 
@@ -142,127 +658,8 @@ pub fn spawn (
     mut physics_state: Mut<physics::State>,
     mut camera_state: Mut<camera::State>,
 ) {
-    let mut level = level_opt.take()
+    let level = level_opt.take()
         .expect("Some level should be loaded");
 
-    // spawn the flag
-    let texture = assets.register("target_flag::texture");
-    let mesh = assets.register("target_flag::mesh");
-
-    println!("{:?} {:?}", mesh, texture);
-
-    world.spawn(Some((
-        Model::from(mesh),
-        Material {
-            texture,
-            ..Default::default()
-        },
-        Transform {
-            translate: Vec3::new(
-                level.target_position.0,
-                level.target_position.1,
-                level.target_position.2
-            ),
-            ..Default::default()
-        },
-        Render::default(),
-    )));
-
-    // spawn level model
-    let texture = assets.register(
-        &[&*level.model, "texture"].join("::")
-    );
-    let mesh_id = assets.register(
-        &[&*level.model, "mesh"].join("::")
-    );
-
-    world.spawn(
-        (pbr::solid::Entity {
-            mesh: mesh_id,
-            texture,
-            ..Default::default()
-        }).some()
-    );
-
-    // add the terrain to the collider set
-    let mesh = assets.get(mesh_id).unwrap();
-
-    let mut indices = Vec::new();
-
-    let vertices = mesh.vertices_as::<[f32; 3]>(0)
-        .collect::<Vec<_>>().iter()
-        .map(|elem| physics::nalgebra::Point3::new(
-                elem[0],
-                elem[1],
-                elem[2],
-            )
-        ).collect();
-
-    let indices_mesh = mesh.indices().take()
-        .expect("terrain mesh should contain indices");
-
-    for i in 0..indices_mesh.len()/3 {
-        indices.push([
-            indices_mesh[i*3],
-            indices_mesh[i*3+1],
-            indices_mesh[i*3+2],
-        ]);
-    }
-
-    let collider = physics::ColliderBuilder::trimesh(
-        vertices,
-        indices,
-    ).build();
-
-    physics_state.physics.as_mut().expect("physics::State must be defined")
-        .collider_set.insert(collider);
-
-    while let Some(object) = level.objects.pop() {
-        match object {
-            Objects::Player(init_state) => {
-                if camera_state.player_entity.is_some() {
-                    println!("Only one player entity is allowed");
-                } else {
-                    camera_state.player_entity = Some(player::spawn(
-                        &mut world,
-                        &mut assets,
-                        &mut physics_state,
-                        Vec3::new(
-                            init_state.position.0,
-                            init_state.position.1,
-                            init_state.position.2
-                        )
-                    ));
-                }
-            },
-            Objects::TimeCapsule(init_state) => {
-                time_capsule::spawn(
-                    &mut world,
-                    &mut assets,
-                    Vec3::new(
-                        init_state.position.0,
-                        init_state.position.1,
-                        init_state.position.2
-                    )
-                )
-            },
-            Objects::Trampoline(init_state) => {
-                trampoline::spawn(
-                    &mut world,
-                    &mut assets,
-                    &mut physics_state,
-                    Vec3::new(
-                        init_state.base_position.0,
-                        init_state.base_position.1,
-                        init_state.base_position.2
-                    ),
-                    Vec3::new(
-                        init_state.button_position.0,
-                        init_state.button_position.1,
-                        init_state.button_position.2
-                    ),
-                )
-            },
-        }
-    }
+    level.spawn(&mut world, &mut assets, &mut physics_state, &mut camera_state);
 }
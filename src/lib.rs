@@ -0,0 +1,25 @@
+pub mod actions;
+pub mod player;
+pub mod ui;
+pub mod camera;
+pub mod physics;
+pub mod level;
+pub mod time;
+pub mod timeline;
+pub mod trampoline;
+pub mod states;
+pub mod time_capsule;
+pub mod ui_clock;
+pub mod ghost;
+pub mod records;
+pub mod simulation;
+pub mod replay;
+pub mod headless;
+pub mod validate;
+pub mod checkpoint;
+pub mod platform;
+pub mod door;
+pub mod crate_box;
+pub mod past_self;
+pub mod signal;
+pub mod light;
@@ -0,0 +1,44 @@
+use dotrix::{
+    World, Color,
+    pbr::{ Light, },
+    math::{ Vec3, },
+};
+
+use crate::signal;
+use crate::time;
+
+/// Used when the intensity is not set in the level file
+pub const DEFAULT_INTENSITY: f32 = 1.0;
+
+/// Light switched by signals, on while a switch targeting it is on
+pub fn spawn(
+    world: &mut World,
+    position: Vec3,
+    color: Color,
+    intensity: f32,
+    receiver: signal::Receiver,
+) {
+    world.spawn(Some((
+        Light::Simple {
+            position,
+            color,
+            intensity,
+            enabled: false,
+        },
+        receiver,
+        time::History::<signal::Receiver>::default(),
+    )));
+}
+
+/// Follows the signals, also while rewinding since the receivers are restored
+pub fn control(
+    world: &World,
+) {
+    let query = world.query::<(&mut Light, &signal::Receiver,)>();
+
+    for (light, receiver) in query {
+        if let Light::Simple { enabled, .. } = light {
+            *enabled = receiver.is_signaled();
+        }
+    }
+}
@@ -3,23 +3,16 @@ use dotrix::{
     Color, World, Input,
     sky::{ skybox, },
     pbr::{ self, Light, },
-    ecs::{ Mut, },
+    ecs::{ Mut, Const, },
     math::Vec3,
     egui, overlay,
     State as StateStack,
 };
 
-mod actions;
-mod player;
-mod ui;
-mod camera;
-mod physics;
-mod level;
-mod time;
-mod trampoline;
-mod states;
-mod time_capsule;
-mod ui_clock;
+use retime::{
+    actions, camera, ghost, level, physics, records, replay,
+    simulation, states, time, ui,
+};
 
 fn main() {
     Dotrix::application("ReTime")
@@ -34,27 +27,27 @@ fn main() {
         .with(System::from(before_init).with(StateStack::on::<states::InitLevel>()))
         .with(System::from(camera::init).with(StateStack::on::<states::InitLevel>()))
         .with(System::from(ui::init).with(StateStack::on::<states::InitLevel>()))
+        .with(System::from(ghost::init).with(StateStack::on::<states::InitLevel>()))
+        .with(System::from(replay::init).with(StateStack::on::<states::InitLevel>()))
         .with(System::from(level::spawn).with(StateStack::on::<states::InitLevel>()))
         .with(System::from(states::after_init).with(StateStack::on::<states::InitLevel>()))
 
-        .with(System::from(time::rewind).with(StateStack::on::<states::RewindTime>()))
-        .with(System::from(time::replay).with(StateStack::on::<states::RunLevel>()))
-        .with(System::from(player::control).with(StateStack::on::<states::RunLevel>()))
-        .with(System::from(trampoline::control).with(StateStack::on::<states::RunLevel>()))
         .with(
-            System::from(states::update)
+            System::from(simulation::run)
                 .with(StateStack::on::<states::RunLevel>())
                 .with(StateStack::on::<states::RewindTime>())
         )
+        .with(System::from(states::restart).with(StateStack::on::<states::Restart>()))
+        .with(System::from(ghost::save).with(StateStack::on::<states::Pause>()))
+        .with(System::from(records::update).with(StateStack::on::<states::Pause>()))
+        .with(System::from(replay::finish).with(StateStack::on::<states::Pause>()))
         .with(
-            System::from(time_capsule::control)
+            System::from(ghost::update)
                 .with(StateStack::on::<states::RunLevel>())
                 .with(StateStack::on::<states::RewindTime>())
         )
-        .with(System::from(time::update_stacks).with(StateStack::on::<states::RunLevel>()))
 
         .with(System::from(physics::update_models))
-        .with(System::from(physics::step).with(StateStack::on::<states::RunLevel>()))
         .with(
             System::from(dotrix::camera::control)
                 .with(StateStack::on::<states::RunLevel>())
@@ -82,9 +75,13 @@ fn main() {
         )
 
         .with(Service::from(physics::State::default()))
+        .with(Service::from(simulation::Clock::default()))
         .with(Service::from(time::Stack::default()))
         .with(Service::from(camera::State::default()))
         .with(Service::from(ui::State::default()))
+        .with(Service::from(ghost::State::default()))
+        .with(Service::from(records::Records::load()))
+        .with(Service::from(replay::State::default()))
         .with(Service::from(None as Option<states::Stats>))
         .with(Service::from(None as Option<level::Level>))
 
@@ -108,12 +105,17 @@ fn startup(
 fn before_init(
     mut world: Mut<World>,
     mut physics_state: Mut<physics::State>,
+    mut clock: Mut<simulation::Clock>,
     mut time_stack: Mut<time::Stack>,
     mut camera_state: Mut<camera::State>,
+    level_opt: Const<Option<level::Level>>,
 ) {
     world.reset();
     *physics_state = physics::State::default();
-    *time_stack = time::Stack::default();
+    *clock = simulation::Clock::default();
+    *time_stack = level_opt.as_ref()
+        .map(|level| level.time_stack())
+        .unwrap_or_default();
     *camera_state = camera::State::default();
 
     init_light(&mut world);
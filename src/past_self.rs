@@ -0,0 +1,183 @@
+use dotrix::{
+    Assets, World, Transform, Color,
+    pbr::{ Model, Material, },
+    math::{ Vec3 },
+    renderer::Render,
+};
+
+use crate::physics::{ self, vector, };
+use crate::player;
+use crate::time;
+
+use std::sync::Arc;
+
+/// Where the past self waits until the first rewound timeline is discarded
+const PARKING_HEIGHT: f32 = -1000.0;
+
+/// Collision group of the past self, it does not collide with the player
+const COLLISION_GROUP: u32 = 0b10;
+
+/// Ball replaying the actions discarded when the player changed the past
+pub struct State {
+    active: bool,
+    actions: Arc<Vec<Option<player::Action>>>,
+    cursor: usize,
+}
+
+impl time::Rewindable for State {
+    type Snapshot = (bool, Arc<Vec<Option<player::Action>>>, usize);
+
+    fn snapshot(&self) -> Self::Snapshot {
+        (self.active, self.actions.clone(), self.cursor)
+    }
+
+    fn restore(&mut self, snapshot: &Self::Snapshot) {
+        self.active = snapshot.0;
+        self.actions = snapshot.1.clone();
+        self.cursor = snapshot.2;
+    }
+}
+
+pub fn spawn(
+    world: &mut World,
+    assets: &mut Assets,
+    physics_state: &mut physics::State,
+) {
+    let state = physics_state.physics.as_mut().expect("physics::State must be defined");
+
+    let texture = assets.register("player::texture");
+    let mesh = assets.register("player::mesh");
+
+    let rigid_body = physics::RigidBodyBuilder::new_dynamic()
+        .translation(vector![0.0, PARKING_HEIGHT, 0.0])
+        .gravity_scale(0.0)
+        .angular_damping(1.0)
+        .build();
+    let collider = physics::ColliderBuilder::ball(player::RADIUS)
+        .restitution(0.7)
+        .collision_groups(physics::InteractionGroups::new(
+            COLLISION_GROUP,
+            !player::COLLISION_GROUP,
+        ))
+        .build();
+    let body_handle = state.rigid_body_set.insert(rigid_body);
+    let collider_handle = state.collider_set.insert_with_parent(
+        collider,
+        body_handle,
+        &mut state.rigid_body_set
+    );
+
+    world.spawn(Some((
+        Model::from(mesh),
+        Material {
+            texture,
+            albedo: Color::rgb(1.0, 0.6, 0.3),
+            ..Default::default()
+        },
+        Transform {
+            translate: Vec3::new(0.0, PARKING_HEIGHT, 0.0),
+            scale: Vec3::new(0.0, 0.0, 0.0),
+            ..Default::default()
+        },
+        Render::default(),
+        State {
+            active: false,
+            actions: Arc::new(Vec::new()),
+            cursor: 0,
+        },
+        time::History::<State>::default(),
+        body_handle,
+        collider_handle,
+    )));
+}
+
+/// Starts replaying the discarded actions from the current player body state
+pub fn activate(
+    world: &World,
+    physics_state: &mut physics::State,
+    actions: Vec<Option<player::Action>>,
+) {
+    let rigid_body_set = &mut physics_state.physics
+        .as_mut().expect("physics::State must be defined")
+        .rigid_body_set;
+
+    let player_body = {
+        let mut query = world.query::<(&physics::RigidBodyHandle, &player::State,)>();
+        match query.next() {
+            Some((rigid_body, _)) => rigid_body_set.get(*rigid_body).unwrap(),
+            None => return,
+        }
+    };
+
+    let position = *player_body.position();
+    let linvel = *player_body.linvel();
+    let angvel = *player_body.angvel();
+
+    let query = world.query::<(&physics::RigidBodyHandle, &mut State,)>();
+
+    for (rigid_body, state) in query {
+        state.active = true;
+        state.actions = Arc::new(actions.clone());
+        state.cursor = 0;
+
+        let body = rigid_body_set.get_mut(*rigid_body).unwrap();
+
+        body.set_position(position, true);
+        body.set_linvel(linvel, true);
+        body.set_angvel(angvel, true);
+        body.set_gravity_scale(1.0, true);
+    }
+}
+
+/// Applies the next replayed action
+pub fn control(
+    world: &World,
+    physics_state: &mut physics::State,
+) {
+    let rigid_body_set = &mut physics_state.physics
+        .as_mut().expect("physics::State must be defined")
+        .rigid_body_set;
+
+    let query = world.query::<(&physics::RigidBodyHandle, &mut State,)>();
+
+    for (rigid_body, state) in query {
+        if !state.active {
+            continue;
+        }
+
+        if let Some(Some(action)) = state.actions.get(state.cursor) {
+            let body = rigid_body_set.get_mut(*rigid_body).unwrap();
+
+            body.apply_torque(action.torque_move*player::TQ_MOVE, true);
+            body.apply_torque(action.torque_rotate*player::TQ_ROTATE, true);
+        }
+
+        state.cursor += 1;
+    }
+
+    update_visibility(world);
+}
+
+/// Hides the past self while it is not active
+pub fn update_visibility(
+    world: &World,
+) {
+    let query = world.query::<(&mut Transform, &State,)>();
+
+    for (transform, state) in query {
+        transform.scale = if state.active {
+            Vec3::new(1.0, 1.0, 1.0)
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+    }
+}
+
+/// Returns the collider of the active past self, used to press switches
+pub fn collider(
+    world: &World,
+) -> Option<physics::ColliderHandle> {
+    let mut query = world.query::<(&physics::ColliderHandle, &State,)>();
+
+    query.find(|(_, state)| state.active).map(|(collider, _)| *collider)
+}
@@ -1,11 +1,18 @@
 pub use rapier3d::prelude::*;
+use rapier3d::crossbeam;
 
 use dotrix::{
     World, Transform,
-    ecs::{ Mut, Context, Const, },
+    assets::Mesh,
+    ecs::{ Const, },
     math::{ Quat, },
 };
 
+use crate::simulation;
+
+use serde::{Serialize, Deserialize};
+use std::collections::{ HashMap, HashSet, };
+
 #[derive(Clone)]
 pub struct PhysicsState {
     pub island_manager: IslandManager,
@@ -29,39 +36,201 @@ impl Default for PhysicsState {
             collider_set: ColliderSet::new(),
             joint_set: JointSet::new(),
             ccd_solver: CCDSolver::new(),
-            integration_parameters: IntegrationParameters::default(),
+            integration_parameters: IntegrationParameters {
+                dt: simulation::TICK_DT,
+                ..Default::default()
+            },
             gravity: vector![0.0, -9.81, 0.0],
         }
     }
 }
 
+/// State of a moving rigid body at a tick
+#[derive(Clone)]
+struct BodyState {
+    handle: RigidBodyHandle,
+    position: Isometry<Real>,
+    linvel: Vector<Real>,
+    angvel: Vector<Real>,
+    gravity_scale: Real,
+}
+
+/// Physics state of a tick for the rewind history: the moving bodies and the
+/// sensor intersections, keyframes also keep the full physics state
+#[derive(Clone)]
+pub struct Frame {
+    keyframe: Option<PhysicsState>,
+    bodies: Vec<BodyState>,
+    intersections: Vec<(ColliderHandle, ColliderHandle)>,
+}
+
+impl Frame {
+    pub fn keyframe(&self) -> Option<&PhysicsState> {
+        self.keyframe.as_ref()
+    }
+}
+
+/// Shape of a sensor collider used to trigger gameplay events
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug)]
+pub enum TriggerShape {
+    Ball { radius: f32 },
+    Cuboid { half_extents: (f32, f32, f32) },
+    Cylinder { half_height: f32, radius: f32 },
+}
+
+impl TriggerShape {
+    fn builder(&self) -> ColliderBuilder {
+        match *self {
+            TriggerShape::Ball { radius } => ColliderBuilder::ball(radius),
+            TriggerShape::Cuboid { half_extents } => ColliderBuilder::cuboid(
+                half_extents.0,
+                half_extents.1,
+                half_extents.2,
+            ),
+            TriggerShape::Cylinder { half_height, radius } =>
+                ColliderBuilder::cylinder(half_height, radius),
+        }
+    }
+}
+
 pub struct State {
     pub physics: Option<PhysicsState>,
+    pipeline: PhysicsPipeline,
+    /// Body positions before the last tick, used to interpolate models
+    previous_positions: HashMap<RigidBodyHandle, Isometry<Real>>,
+    /// Intersection events of the last tick; after a restore they describe
+    /// the change to the intersections of the restored frame
+    pub events: Vec<IntersectionEvent>,
+    /// Sensor intersections kept up to date from the events, stored in both
+    /// orders
+    intersections: HashSet<(ColliderHandle, ColliderHandle)>,
+    /// The narrow phase was rebuilt from a keyframe, its events of the next
+    /// step are relative to the keyframe tick
+    restored: bool,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
             physics: Some(PhysicsState::default()),
+            pipeline: PhysicsPipeline::new(),
+            previous_positions: HashMap::new(),
+            events: Vec::new(),
+            intersections: HashSet::new(),
+            restored: false,
         }
     }
 }
 
-pub struct Properties {
-    pipeline: PhysicsPipeline,
-}
+impl State {
+    /// Drops interpolation history, e.g. after the state was restored from the time stack
+    pub fn snap_previous_positions(&mut self) {
+        self.previous_positions.clear();
+    }
 
-impl Default for Properties {
-    fn default() -> Self {
-        Self {
-            pipeline: PhysicsPipeline::new(),
+    /// Captures the state of the moving bodies, with the full physics state
+    /// if a keyframe is requested
+    pub fn frame(&self, keyframe: bool) -> Frame {
+        let physics = self.physics.as_ref().expect("physics::State must be defined");
+
+        let bodies = physics.rigid_body_set.iter()
+            .filter(|(_, body)| !body.is_static())
+            .map(|(handle, body)| BodyState {
+                handle,
+                position: *body.position(),
+                linvel: *body.linvel(),
+                angvel: *body.angvel(),
+                gravity_scale: body.gravity_scale(),
+            })
+            .collect();
+
+        Frame {
+            keyframe: if keyframe { Some(physics.clone()) } else { None },
+            bodies,
+            intersections: self.intersections.iter().copied().collect(),
+        }
+    }
+
+    /// Rebuilds the physics state of a frame from a keyframe of the same level
+    pub fn restore_frame(&mut self, frame: &Frame, keyframe: &PhysicsState) {
+        let mut physics = frame.keyframe.as_ref().unwrap_or(keyframe).clone();
+
+        for state in frame.bodies.iter() {
+            if let Some(body) = physics.rigid_body_set.get_mut(state.handle) {
+                body.set_position(state.position, true);
+                if body.is_kinematic() {
+                    body.set_next_kinematic_position(state.position);
+                }
+                body.set_linvel(state.linvel, true);
+                body.set_angvel(state.angvel, true);
+                body.set_gravity_scale(state.gravity_scale, true);
+            }
         }
+
+        self.physics = Some(physics);
+
+        let intersections = frame.intersections.iter().copied().collect();
+        self.events = intersection_changes(&self.intersections, &intersections);
+        self.intersections = intersections;
+        self.restored = true;
+    }
+
+    /// Time step of the next tick, scaled down in slow motion
+    pub fn dt(&self) -> f32 {
+        self.physics.as_ref().expect("physics::State must be defined")
+            .integration_parameters.dt
+    }
+
+    /// Whether the colliders intersect, as reported by the events so far
+    pub fn is_intersecting(&self, collider1: ColliderHandle, collider2: ColliderHandle) -> bool {
+        self.intersections.contains(&(collider1, collider2))
+    }
+
+    /// Whether the colliders started to intersect during the last tick
+    pub fn intersection_started(&self, collider1: ColliderHandle, collider2: ColliderHandle) -> bool {
+        self.events.iter().any(|event| event.intersecting && (
+            (event.collider1 == collider1 && event.collider2 == collider2) ||
+            (event.collider1 == collider2 && event.collider2 == collider1)
+        ))
+    }
+
+    /// Adds a static sensor collider reporting intersection events
+    pub fn insert_sensor(&mut self, shape: TriggerShape, position: Vector<Real>) -> ColliderHandle {
+        let collider = shape.builder()
+            .sensor(true)
+            .active_events(ActiveEvents::INTERSECTION_EVENTS)
+            .translation(position)
+            .build();
+
+        self.physics.as_mut().expect("physics::State must be defined")
+            .collider_set.insert(collider)
     }
 }
 
+/// Returns a builder of a triangle mesh collider with the shape of the mesh
+pub fn trimesh_collider(mesh: &Mesh) -> ColliderBuilder {
+    let vertices = mesh.vertices_as::<[f32; 3]>(0)
+        .map(|elem| nalgebra::Point3::new(elem[0], elem[1], elem[2]))
+        .collect();
+
+    let indices_mesh = mesh.indices().take()
+        .expect("collider mesh should contain indices");
+
+    let indices = indices_mesh.chunks_exact(3)
+        .map(|triangle| [triangle[0], triangle[1], triangle[2]])
+        .collect();
+
+    ColliderBuilder::trimesh(vertices, indices)
+}
+
+/// Sensor collider of a gameplay entity
+#[derive(Clone, Copy, std::fmt::Debug)]
+pub struct Sensor {
+    pub collider: ColliderHandle,
+}
+
 pub fn step(
-    mut props: Context<Properties>,
-    mut state: Mut<State>,
+    state: &mut State,
 ) {
     let PhysicsState {
         mut island_manager,
@@ -76,9 +245,16 @@ pub fn step(
       } = state.physics.take().expect("physics::State must be defined");
 
     let physics_hooks = ();
-    let event_handler = ();
 
-    props.pipeline.step(
+    let (intersection_send, intersection_recv) = crossbeam::channel::unbounded();
+    let (contact_send, _contact_recv) = crossbeam::channel::unbounded();
+    let event_handler = ChannelEventCollector::new(intersection_send, contact_send);
+
+    state.previous_positions = rigid_body_set.iter()
+        .map(|(handle, body)| (handle, *body.position()))
+        .collect();
+
+    state.pipeline.step(
         &gravity,
         &integration_parameters,
         &mut island_manager,
@@ -92,6 +268,31 @@ pub fn step(
         &event_handler,
     );
 
+    state.events.clear();
+
+    if state.restored {
+        // the events of a rebuilt narrow phase are relative to its keyframe,
+        // compare with the intersections of the restored frame instead
+        let intersections = sensor_intersections(&narrow_phase, &collider_set);
+        state.events = intersection_changes(&state.intersections, &intersections);
+        state.intersections = intersections;
+        state.restored = false;
+    } else {
+        while let Ok(event) = intersection_recv.try_recv() {
+            let pairs = [(event.collider1, event.collider2), (event.collider2, event.collider1)];
+
+            if event.intersecting {
+                state.intersections.extend(pairs);
+            } else {
+                for pair in pairs.iter() {
+                    state.intersections.remove(pair);
+                }
+            }
+
+            state.events.push(event);
+        }
+    }
+
     state.physics = Some(PhysicsState {
         island_manager,
         broad_phase,
@@ -105,9 +306,46 @@ pub fn step(
     });
 }
 
+/// Returns the intersecting pairs of sensors and colliders in both orders
+fn sensor_intersections(
+    narrow_phase: &NarrowPhase,
+    collider_set: &ColliderSet,
+) -> HashSet<(ColliderHandle, ColliderHandle)> {
+    collider_set.iter()
+        .filter(|(_, collider)| collider.is_sensor())
+        .flat_map(|(handle, _)| narrow_phase.intersections_with(handle))
+        .filter(|(_, _, intersecting)| *intersecting)
+        .flat_map(|(collider1, collider2, _)| [(collider1, collider2), (collider2, collider1)])
+        .collect()
+}
+
+/// Returns the events turning the old intersections into the new ones, one
+/// per pair
+fn intersection_changes(
+    old: &HashSet<(ColliderHandle, ColliderHandle)>,
+    new: &HashSet<(ColliderHandle, ColliderHandle)>,
+) -> Vec<IntersectionEvent> {
+    let started = new.difference(old).map(|pair| (pair, true));
+    let stopped = old.difference(new).map(|pair| (pair, false));
+
+    let mut events: Vec<IntersectionEvent> = Vec::new();
+
+    for (&(collider1, collider2), intersecting) in started.chain(stopped) {
+        let reported = events.iter()
+            .any(|event| event.collider1 == collider2 && event.collider2 == collider1);
+
+        if !reported {
+            events.push(IntersectionEvent::new(collider1, collider2, intersecting));
+        }
+    }
+
+    events
+}
+
 pub fn update_models(
     world: Const<World>,
     state: Const<State>,
+    clock: Const<simulation::Clock>,
 ) {
 
     let query = world.query::<(
@@ -121,8 +359,14 @@ pub fn update_models(
 
         let body = rigid_body_set.get(*rigid_body).unwrap();
 
-        let position = body.position().translation;
-        let rotation = body.position().rotation;
+        // interpolate between the last two ticks
+        let isometry = match state.previous_positions.get(rigid_body) {
+            Some(previous) => previous.lerp_slerp(body.position(), clock.alpha),
+            None => *body.position(),
+        };
+
+        let position = isometry.translation;
+        let rotation = isometry.rotation;
 
         // update model transfrom
         transform.translate.x = position.x;
@@ -0,0 +1,200 @@
+use dotrix::{
+    Assets, World, Transform,
+    pbr::{ Model, Material, },
+    math::{ Vec3, },
+    renderer::Render,
+};
+
+use crate::physics::{ self, vector, };
+use crate::signal;
+use crate::time;
+
+/// Used for segments without a speed set in the level file
+pub const DEFAULT_SPEED: f32 = 2.0;
+
+/// Closed path through the waypoints, the platform waits at every waypoint
+/// and then moves to the next one with the speed of the segment
+pub struct Path {
+    waypoints: Vec<Vec3>,
+    speeds: Vec<f32>,
+    wait_times: Vec<f32>,
+}
+
+impl Path {
+    pub fn new(waypoints: Vec<Vec3>, speeds: Vec<f32>, wait_times: Vec<f32>) -> Self {
+        Self {
+            waypoints,
+            speeds,
+            wait_times,
+        }
+    }
+
+    fn speed(&self, segment: usize) -> f32 {
+        self.speeds.get(segment)
+            .or(self.speeds.last())
+            .copied()
+            .unwrap_or(DEFAULT_SPEED)
+            .max(f32::EPSILON)
+    }
+
+    fn wait_time(&self, waypoint: usize) -> f32 {
+        self.wait_times.get(waypoint).copied().unwrap_or(0.0).max(0.0)
+    }
+
+    fn segment_time(&self, segment: usize) -> f32 {
+        let from = self.waypoints[segment];
+        let to = self.waypoints[(segment + 1) % self.waypoints.len()];
+        let length = (
+            (to.x - from.x).powf(2.0) +
+            (to.y - from.y).powf(2.0) +
+            (to.z - from.z).powf(2.0)
+        ).sqrt();
+
+        length / self.speed(segment)
+    }
+
+    /// Position on the path after the given time since the level start
+    pub fn position_at(&self, time: f32) -> Vec3 {
+        let count = self.waypoints.len();
+
+        if count < 2 {
+            return self.waypoints.first().copied().unwrap_or(Vec3::new(0.0, 0.0, 0.0));
+        }
+
+        let cycle = (0..count)
+            .map(|i| self.wait_time(i) + self.segment_time(i))
+            .sum::<f32>();
+
+        if cycle <= 0.0 {
+            return self.waypoints[0];
+        }
+
+        let mut t = time % cycle;
+
+        for i in 0..count {
+            let wait_time = self.wait_time(i);
+            if t < wait_time {
+                return self.waypoints[i];
+            }
+            t -= wait_time;
+
+            let segment_time = self.segment_time(i);
+            if t < segment_time {
+                let from = self.waypoints[i];
+                let to = self.waypoints[(i + 1) % count];
+                return from + (to - from) * (t / segment_time);
+            }
+            t -= segment_time;
+        }
+
+        self.waypoints[0]
+    }
+}
+
+pub struct State {
+    path: Path,
+    /// Time the platform has been moving, the position is derived from it
+    time: f32,
+    /// The platform only moves while it receives a signal
+    wait_for_signal: bool,
+}
+
+impl time::Rewindable for State {
+    type Snapshot = f32;
+
+    fn snapshot(&self) -> f32 {
+        self.time
+    }
+
+    fn restore(&mut self, time: &f32) {
+        self.time = *time;
+    }
+}
+
+pub fn spawn(
+    world: &mut World,
+    assets: &mut Assets,
+    physics_state: &mut physics::State,
+    model: &str,
+    path: Path,
+    receiver: signal::Receiver,
+    wait_for_signal: bool,
+    echo: bool,
+) {
+    let state = physics_state.physics.as_mut().expect("physics::State must be defined");
+
+    let texture = assets.register(&[model, "texture"].join("::"));
+    let mesh_id = assets.register(&[model, "mesh"].join("::"));
+
+    let start = path.position_at(0.0);
+
+    let rigid_body = physics::RigidBodyBuilder::new_kinematic_position_based()
+        .translation(vector![start.x, start.y, start.z])
+        .build();
+    let body_handle = state.rigid_body_set.insert(rigid_body);
+
+    // add platform to the collider set
+    let mesh = assets.get(mesh_id).unwrap();
+
+    let collider = physics::trimesh_collider(mesh).build();
+
+    state.collider_set.insert_with_parent(
+        collider,
+        body_handle,
+        &mut state.rigid_body_set
+    );
+
+    let history = if echo {
+        time::History::<State>::echo()
+    } else {
+        time::History::<State>::default()
+    };
+
+    world.spawn(Some((
+        Model::from(mesh_id),
+        Material {
+            texture,
+            ..Default::default()
+        },
+        Transform {
+            translate: start,
+            ..Default::default()
+        },
+        Render::default(),
+        State {
+            path,
+            time: 0.0,
+            wait_for_signal,
+        },
+        receiver,
+        time::Echo { enabled: echo },
+        history,
+        time::History::<signal::Receiver>::default(),
+        body_handle,
+    )));
+}
+
+pub fn control(
+    world: &World,
+    physics_state: &mut physics::State,
+) {
+    // follows the slow motion
+    let dt = physics_state.dt();
+    let physics = physics_state.physics
+        .as_mut().expect("physics::State must be defined");
+
+    let rigid_body_set = &mut physics.rigid_body_set;
+
+    let query = world.query::<(&physics::RigidBodyHandle, &mut State, &signal::Receiver,)>();
+
+    for (rigid_body, state, receiver) in query {
+        if !state.wait_for_signal || receiver.is_signaled() {
+            state.time += dt;
+        }
+
+        let position = state.path.position_at(state.time);
+        let body = rigid_body_set.get_mut(*rigid_body).unwrap();
+
+        body.set_next_kinematic_translation(vector![position.x, position.y, position.z]);
+    }
+}
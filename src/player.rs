@@ -1,8 +1,8 @@
 use dotrix::{
-    Assets, World, Transform, Input, Id,
+    Assets, World, Transform, Id,
     assets::Mesh,
     pbr::{ Model, Material, },
-    ecs::{ Mut, Const, Entity, },
+    ecs::{ Entity, },
     math::{ Vec3 },
     renderer::Render,
 };
@@ -16,41 +16,32 @@ use crate::physics::{
     nalgebra,
     Vector,
     Real,
+    Isometry,
 };
 
 use std::f32::consts::PI;
-use std::collections::VecDeque;
 
-const TQ_MOVE:   f32 = 25.0;
-const TQ_ROTATE: f32 = 5.0;
+pub const TQ_MOVE:   f32 = 25.0;
+pub const TQ_ROTATE: f32 = 5.0;
 
-pub struct State {
-    pub fwd_angle: f32,
-    pub current_action: Option<Action>,
-    pub action_stack: VecDeque<Option<Action>>,
-}
+pub const RADIUS: f32 = 1.0;
 
-impl State {
-    pub fn clear_action_stack(&mut self, time_stack: &mut time::Stack) {
-        for i in time_stack.index_cleared..time_stack.index {
-            self.action_stack[i] = None;
-        }
+/// Collision group of the player ball
+pub const COLLISION_GROUP: u32 = 0b01;
 
-        time_stack.index_cleared = time_stack.index;
-    }
+pub struct State {
+    pub fwd_angle: f32,
 }
 
 impl Default for State {
     fn default() -> Self {
         Self {
             fwd_angle: 0.0,
-            current_action: None,
-            action_stack: VecDeque::new(),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct Action {
     pub torque_move: Vector<Real>,
     pub torque_rotate: Vector<Real>,
@@ -79,9 +70,12 @@ pub fn spawn(
         .translation(physics::vector![0.0, 10.0, 0.0])
         .angular_damping(1.0)
         .build();
-    let collider = physics::ColliderBuilder::ball(1.0).restitution(0.7).build();
+    let collider = physics::ColliderBuilder::ball(RADIUS)
+        .restitution(0.7)
+        .collision_groups(physics::InteractionGroups::new(COLLISION_GROUP, u32::MAX))
+        .build();
     let ball_body_handle = state.rigid_body_set.insert(rigid_body);
-    state.collider_set.insert_with_parent(
+    let ball_collider_handle = state.collider_set.insert_with_parent(
         collider,
         ball_body_handle,
         &mut state.rigid_body_set
@@ -98,28 +92,57 @@ pub fn spawn(
             ..Default::default()
         },
         Render::default(),
-        time::ActionableObject {
-            active: true,
-            selected: true,
-            is_player: true,
-            tile_texture_name: "player",
-        },
+        time::ActionableObject::new("player", true),
+        time::History::<time::ActionableObject>::default(),
         State::default(),
         ball_body_handle,
+        ball_collider_handle,
     ))).first()
 }
 
+/// Returns the player position in the physics world
+pub fn pose(
+    world: &World,
+    physics_state: &physics::State,
+) -> Option<Isometry<Real>> {
+    let rigid_body_set = &physics_state.physics.as_ref()
+        .expect("physics::State must be defined")
+        .rigid_body_set;
+
+    let mut query = world.query::<(&physics::RigidBodyHandle, &State,)>();
+
+    query.next().map(|(rigid_body, _)|
+        *rigid_body_set.get(*rigid_body).unwrap().position()
+    )
+}
+
+/// Returns the player collider, used to check sensor intersections
+pub fn collider(
+    world: &World,
+) -> Option<physics::ColliderHandle> {
+    let mut query = world.query::<(&physics::ColliderHandle, &State,)>();
+
+    query.next().map(|(collider, _)| *collider)
+}
+
+/// Returns the radius of a sensor ball that triggers when the player center is
+/// within the given distance, which has to be over the ball radius
+pub fn sensor_radius(distance: f32) -> f32 {
+    (distance - RADIUS).max(0.05)
+}
+
 pub fn control(
-    world: Const<World>,
-    input: Const<Input>,
-    camera: Const<dotrix::Camera>,
-    mut physics_state: Mut<physics::State>,
-    mut time_stack: Mut<time::Stack>,
+    world: &World,
+    input: &actions::TickInput,
+    physics_state: &mut physics::State,
+    time_stack: &mut time::Stack,
 ) {
     let query = world.query::<(
-        &physics::RigidBodyHandle, &mut State, & time::ActionableObject
+        &physics::RigidBodyHandle, &mut State, &mut time::ActionableObject
     )>();
 
+    let mut new_action = false;
+
     for (rigid_body, state, object) in query {
 
         let rigid_body_set = &mut physics_state.physics
@@ -129,7 +152,7 @@ pub fn control(
         let body = rigid_body_set.get_mut(*rigid_body).unwrap();
 
         // align forward direction with the camera view
-        state.fwd_angle = PI - camera.pan;
+        state.fwd_angle = PI - input.camera_pan;
 
         let fwd_dir = vector![-state.fwd_angle.sin(), 0.0, -state.fwd_angle.cos()];
         let left_dir = vector![-state.fwd_angle.cos(), 0.0, state.fwd_angle.sin()];
@@ -168,20 +191,25 @@ pub fn control(
         }
 
         if is_any_action {
-            state.clear_action_stack(&mut time_stack);
+            new_action = true;
         } else {
-            if let Some(current_action) = state.current_action.take() {
-                torque_move = current_action.torque_move;
-                torque_rotate = current_action.torque_rotate;
+            if let Some(time::ObjectAction::Roll(action)) = object.current_action.take() {
+                torque_move = action.torque_move;
+                torque_rotate = action.torque_rotate;
             }
         }
 
-        state.current_action = Some(Action {
+        object.current_action = Some(time::ObjectAction::Roll(Action {
             torque_move,
             torque_rotate,
-        });
+        }));
 
         body.apply_torque(torque_move*TQ_MOVE, true);
         body.apply_torque(torque_rotate*TQ_ROTATE, true);
     }
+
+    // an action given while the time is replayed changes the past
+    if new_action {
+        time::change_past(world, time_stack, physics_state);
+    }
 }
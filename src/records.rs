@@ -0,0 +1,95 @@
+use dotrix::ecs::{ Const, Mut, };
+
+use crate::replay;
+use crate::states;
+
+use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::{env, fs, path};
+
+const SAVE_FILE: &str = "save.yaml";
+
+/// Folder of the save file, ghosts and replays: `$XDG_DATA_HOME/retime` if
+/// set, otherwise the folder of the executable
+pub fn data_dir() -> path::PathBuf {
+    if let Some(data_home) = env::var_os("XDG_DATA_HOME").filter(|dir| !dir.is_empty()) {
+        return path::PathBuf::from(data_home).join("retime");
+    }
+
+    env::current_exe().ok()
+        .and_then(|exe| exe.parent().map(|dir| dir.to_path_buf()))
+        .unwrap_or_else(|| path::PathBuf::from("."))
+}
+
+#[derive(Serialize, Deserialize, Default, std::fmt::Debug)]
+pub struct LevelRecord {
+    pub best_time: Option<f32>,
+    pub last_time: Option<f32>,
+    pub completions: u32,
+}
+
+/// Player progress per level file name, persisted in the save file
+#[derive(Serialize, Deserialize, Default, std::fmt::Debug)]
+pub struct Records {
+    levels: BTreeMap<String, LevelRecord>,
+}
+
+impl Records {
+    pub fn load() -> Self {
+        fs::read_to_string(data_dir().join(SAVE_FILE)).ok()
+            .and_then(|s| serde_yaml::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) {
+        let result = fs::create_dir_all(data_dir())
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_yaml::to_string(self).map_err(|e| e.to_string()))
+            .and_then(|s| fs::write(data_dir().join(SAVE_FILE), s)
+                .map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            println!("Cannot save the game records! {}", e);
+        }
+    }
+
+    pub fn get(&self, level_name: &str) -> Option<&LevelRecord> {
+        self.levels.get(level_name)
+    }
+
+    /// Stores a finished run and returns true if it is a new best time
+    pub fn add_run(&mut self, level_name: &str, time: f32) -> bool {
+        let record = self.levels.entry(level_name.to_string()).or_default();
+
+        let new_record = match record.best_time {
+            Some(best_time) => time < best_time,
+            None => true,
+        };
+
+        if new_record {
+            record.best_time = Some(time);
+        }
+        record.last_time = Some(time);
+        record.completions += 1;
+
+        new_record
+    }
+}
+
+pub fn update(
+    mut stats_opt: Mut<Option<states::Stats>>,
+    mut records: Mut<Records>,
+    replay_state: Const<replay::State>,
+) {
+    let stats = stats_opt.as_mut()
+        .expect("Game stats should be initialized");
+
+    // a watched replay is not a new completion
+    if !stats.level_passed || stats.recorded || replay_state.is_playing() {
+        return;
+    }
+
+    stats.recorded = true;
+    stats.new_record = records.add_run(&stats.level_name, stats.time);
+    records.save();
+}
@@ -0,0 +1,166 @@
+use dotrix::{
+    World,
+    ecs::{ Mut, Const, },
+};
+
+use crate::actions::TickInput;
+use crate::physics;
+use crate::player;
+use crate::records;
+use crate::states;
+
+use serde::{Serialize, Deserialize};
+use std::{fs, path};
+
+const REPLAY_FOLDER: &str = "replays";
+
+/// Per-tick inputs of a finished run, enough to reproduce it from the level file
+#[derive(Serialize, Deserialize, std::fmt::Debug)]
+pub struct Replay {
+    pub level_name: String,
+    pub ticks: Vec<TickInput>,
+    pub final_position: (f32, f32, f32),
+    pub checksum: u64,
+}
+
+impl Replay {
+    fn file_path(level_name: &str) -> path::PathBuf {
+        records::data_dir().join(REPLAY_FOLDER).join([level_name, ".yaml"].join(""))
+    }
+
+    pub fn exists(level_name: &str) -> bool {
+        Self::file_path(level_name).exists()
+    }
+
+    pub fn load(level_name: &str) -> Option<Self> {
+        let s = fs::read_to_string(Self::file_path(level_name)).ok()?;
+        serde_yaml::from_str(&s).ok()
+    }
+
+    pub fn save(&self) {
+        let result = fs::create_dir_all(records::data_dir().join(REPLAY_FOLDER))
+            .map_err(|e| e.to_string())
+            .and_then(|_| serde_yaml::to_string(self).map_err(|e| e.to_string()))
+            .and_then(|s| fs::write(Self::file_path(&self.level_name), s)
+                .map_err(|e| e.to_string()));
+
+        if let Err(e) = result {
+            println!("Cannot save the replay! {}", e);
+        }
+    }
+}
+
+/// FNV-1a hash of the exact bits of a position
+pub fn checksum(position: (f32, f32, f32)) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+
+    for value in [position.0, position.1, position.2].iter() {
+        for byte in value.to_bits().to_le_bytes().iter() {
+            hash ^= *byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    hash
+}
+
+pub struct State {
+    recorded: Vec<TickInput>,
+    playback: Option<Replay>,
+    cursor: usize,
+    finish_position: Option<(f32, f32, f32)>,
+    /// Result of comparing a played back run with its recorded checksum
+    pub desynced: Option<bool>,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        Self {
+            recorded: Vec::new(),
+            playback: None,
+            cursor: 0,
+            finish_position: None,
+            desynced: None,
+        }
+    }
+}
+
+impl State {
+    pub fn play(&mut self, replay: Option<Replay>) {
+        self.playback = replay;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Returns the input of the next tick, either recorded or played back
+    pub fn next_input(&mut self, sampled: TickInput) -> TickInput {
+        match &self.playback {
+            Some(replay) => {
+                let input = replay.ticks.get(self.cursor).cloned().unwrap_or_default();
+                self.cursor += 1;
+                input
+            },
+            None => {
+                self.recorded.push(sampled.clone());
+                sampled
+            },
+        }
+    }
+
+    pub fn save(&self, level_name: &str) {
+        if let Some(final_position) = self.finish_position {
+            Replay {
+                level_name: level_name.to_string(),
+                ticks: self.recorded.clone(),
+                final_position,
+                checksum: checksum(final_position),
+            }.save();
+        }
+    }
+}
+
+pub fn init(
+    mut state: Mut<State>,
+) {
+    state.recorded.clear();
+    state.cursor = 0;
+    state.finish_position = None;
+    state.desynced = None;
+}
+
+pub fn finish(
+    world: Const<World>,
+    physics_state: Const<physics::State>,
+    stats_opt: Const<Option<states::Stats>>,
+    mut state: Mut<State>,
+) {
+    let stats = stats_opt.as_ref()
+        .expect("Game stats should be initialized");
+
+    if !stats.level_passed || state.finish_position.is_some() {
+        return;
+    }
+
+    let position = match player::pose(&world, &physics_state) {
+        Some(pose) => (pose.translation.x, pose.translation.y, pose.translation.z),
+        None => return,
+    };
+
+    state.finish_position = Some(position);
+
+    if let Some(replay) = &state.playback {
+        let desynced = state.cursor != replay.ticks.len() ||
+            checksum(position) != replay.checksum;
+
+        if desynced {
+            println!(
+                "Replay desync: expected {:?}, finished at {:?}",
+                replay.final_position, position
+            );
+        }
+
+        state.desynced = Some(desynced);
+    }
+}
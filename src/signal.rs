@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::f32::consts::PI;
+
+use dotrix::{
+    Assets, World, Transform, Color,
+    pbr::{ Model, Material, },
+    math::{ Quat, Vec3, },
+    renderer::Render,
+};
+
+use crate::past_self;
+use crate::physics::{ self, vector, };
+use crate::player;
+use crate::time;
+use crate::time_capsule;
+use crate::trampoline;
+
+use serde::{Serialize, Deserialize};
+
+/// What makes a switch emit its signal
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug)]
+pub enum SwitchKind {
+    /// Stays on once the player touched it
+    Button,
+    /// Item collected by the player, stays on once collected
+    Key,
+    /// On while the player is on it
+    PressurePlate,
+    /// On for `duration` seconds every `period` seconds
+    Timer { period: f32, duration: f32 },
+}
+
+/// Emits a signal to the objects with the target ids while it is on
+pub struct Switch {
+    kind: SwitchKind,
+    targets: Vec<String>,
+    sensor: Option<physics::ColliderHandle>,
+    on: bool,
+    time: f32,
+}
+
+impl Switch {
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+}
+
+impl time::Rewindable for Switch {
+    type Snapshot = (bool, f32);
+
+    fn snapshot(&self) -> (bool, f32) {
+        (self.on, self.time)
+    }
+
+    fn restore(&mut self, snapshot: &(bool, f32)) {
+        self.on = snapshot.0;
+        self.time = snapshot.1;
+    }
+}
+
+/// Receives signals addressed to the object id
+pub struct Receiver {
+    id: Option<String>,
+    signaled: bool,
+    previous: bool,
+}
+
+impl Receiver {
+    pub fn new(id: Option<String>) -> Self {
+        Self {
+            id,
+            signaled: false,
+            previous: false,
+        }
+    }
+
+    /// Returns true if the receiver has an id, i.e. can be a target
+    pub fn has_id(&self) -> bool {
+        self.id.is_some()
+    }
+
+    /// Returns true while any of the switches targeting this object is on
+    pub fn is_signaled(&self) -> bool {
+        self.signaled
+    }
+
+    /// Returns true on the tick the signal turns on
+    pub fn is_activated(&self) -> bool {
+        self.signaled && !self.previous
+    }
+}
+
+impl time::Rewindable for Receiver {
+    type Snapshot = (bool, bool);
+
+    fn snapshot(&self) -> (bool, bool) {
+        (self.signaled, self.previous)
+    }
+
+    fn restore(&mut self, snapshot: &(bool, bool)) {
+        self.signaled = snapshot.0;
+        self.previous = snapshot.1;
+    }
+}
+
+const KEY_SCALE: f32 = 0.3;
+/// Same as the time capsule distance, just over the ball radius
+const KEY_MIN_DIST: f32 = 1.1;
+
+/// Switches reuse the trampoline button model, keys reuse the time capsule model
+pub fn spawn(
+    world: &mut World,
+    assets: &mut Assets,
+    physics_state: &mut physics::State,
+    position: Vec3,
+    kind: SwitchKind,
+    targets: Vec<String>,
+    trigger: Option<physics::TriggerShape>,
+) {
+    let (model, material, transform, min_dist) = match kind {
+        SwitchKind::Key => (
+            time_capsule::DEFAULT_MODEL,
+            Material {
+                albedo: Color::rgb(1.0, 0.8, 0.2),
+                ..Default::default()
+            },
+            Transform {
+                translate: position,
+                scale: Vec3::new(KEY_SCALE, KEY_SCALE, KEY_SCALE),
+                ..Default::default()
+            },
+            KEY_MIN_DIST,
+        ),
+        _ => (
+            "trampoline",
+            Material::default(),
+            Transform {
+                translate: position,
+                rotate: Quat::new((PI/4.0).cos(), 0.0, 0.0, (PI/4.0).sin()),
+                scale: Vec3::new(0.4, 1.0, 0.4)
+            },
+            trampoline::BUTTON_MIN_DIST,
+        ),
+    };
+
+    let texture = assets.register(&[model, "texture"].join("::"));
+    let mesh = assets.register(&[model, "mesh"].join("::"));
+
+    let sensor = match kind {
+        SwitchKind::Timer { .. } => None,
+        _ => Some(physics_state.insert_sensor(
+            trigger.unwrap_or(physics::TriggerShape::Ball {
+                radius: player::sensor_radius(min_dist),
+            }),
+            vector![position.x, position.y, position.z],
+        )),
+    };
+
+    world.spawn(Some((
+        Model::from(mesh),
+        Material {
+            texture,
+            ..material
+        },
+        transform,
+        Render::default(),
+        Switch {
+            kind,
+            targets,
+            sensor,
+            on: false,
+            time: 0.0,
+        },
+        time::History::<Switch>::default(),
+    )));
+}
+
+/// Updates the switches and delivers their signals to the receivers
+pub fn control(
+    world: &World,
+    physics_state: &physics::State,
+) {
+    let colliders = player::collider(world).into_iter()
+        .chain(past_self::collider(world))
+        .collect::<Vec<_>>();
+
+    let mut signaled = HashSet::new();
+
+    let query = world.query::<(&mut Switch, &mut Transform,)>();
+    let dt = physics_state.dt();
+
+    for (switch, transform) in query {
+        switch.time += dt;
+
+        // switches are pressed by the player and the past self
+        let touched = switch.sensor.map(|sensor| colliders.iter()
+            .any(|collider| physics_state.is_intersecting(*collider, sensor))
+        ).unwrap_or(false);
+        let entered = switch.sensor.map(|sensor| colliders.iter()
+            .any(|collider| physics_state.intersection_started(*collider, sensor))
+        ).unwrap_or(false);
+
+        switch.on = match switch.kind {
+            SwitchKind::Button | SwitchKind::Key => switch.on || entered,
+            SwitchKind::PressurePlate => touched,
+            SwitchKind::Timer { period, duration } =>
+                period > 0.0 && switch.time % period < duration,
+        };
+
+        if switch.on {
+            signaled.extend(switch.targets.iter().cloned());
+        }
+
+        match (switch.kind, switch.on) {
+            // collected keys are hidden
+            (SwitchKind::Key, true) => transform.scale = Vec3::new(0.0, 0.0, 0.0),
+            (SwitchKind::Key, false) => transform.scale = Vec3::new(KEY_SCALE, KEY_SCALE, KEY_SCALE),
+            (_, true) => transform.rotate = Quat::new(
+                (3.0*PI/4.0).cos(), 0.0, 0.0, (3.0*PI/4.0).sin()
+            ),
+            (_, false) => transform.rotate = Quat::new(
+                (PI/4.0).cos(), 0.0, 0.0, (PI/4.0).sin()
+            ),
+        }
+    }
+
+    let query = world.query::<(&mut Receiver,)>();
+
+    for (receiver,) in query {
+        receiver.previous = receiver.signaled;
+        receiver.signaled = receiver.id.as_ref()
+            .map(|id| signaled.contains(id))
+            .unwrap_or(false);
+    }
+}
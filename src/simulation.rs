@@ -0,0 +1,176 @@
+use dotrix::{
+    World, Input, Frame,
+    ecs::{ Mut, Const, },
+    State as StateStack,
+};
+
+use crate::actions;
+use crate::checkpoint;
+use crate::door;
+use crate::ghost;
+use crate::light;
+use crate::past_self;
+use crate::physics;
+use crate::platform;
+use crate::player;
+use crate::replay;
+use crate::signal;
+use crate::states;
+use crate::time;
+use crate::time_capsule;
+use crate::trampoline;
+
+pub const TICK_RATE: f32 = 60.0;
+pub const TICK_DT: f32 = 1.0 / TICK_RATE;
+
+/// Ticks above this number per frame are dropped to keep slow machines responsive
+const MAX_TICKS_PER_FRAME: usize = 5;
+
+pub struct Clock {
+    accumulator: f32,
+    /// Fraction of a tick elapsed since the last one, used to interpolate models
+    pub alpha: f32,
+    sampler: actions::Sampler,
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self {
+            accumulator: 0.0,
+            alpha: 0.0,
+            sampler: actions::Sampler::default(),
+        }
+    }
+}
+
+pub fn run(
+    world: Const<World>,
+    input: Const<Input>,
+    camera: Const<dotrix::Camera>,
+    frame: Const<Frame>,
+    mut clock: Mut<Clock>,
+    mut physics_state: Mut<physics::State>,
+    mut time_stack: Mut<time::Stack>,
+    mut stats_opt: Mut<Option<states::Stats>>,
+    mut ghost_state: Mut<ghost::State>,
+    mut replay_state: Mut<replay::State>,
+    mut state_stack: Mut<StateStack>,
+) {
+    clock.sampler.collect(&input);
+    clock.accumulator += frame.delta().as_secs_f32();
+
+    let mut ticks = 0;
+
+    while clock.accumulator >= TICK_DT {
+        if ticks >= MAX_TICKS_PER_FRAME {
+            clock.accumulator = 0.0;
+            break;
+        }
+
+        if !is_running(&state_stack) {
+            clock.accumulator = 0.0;
+            break;
+        }
+
+        let sampled = clock.sampler.sample(&input, camera.pan);
+        let tick_input = replay_state.next_input(sampled);
+
+        tick(
+            &world,
+            &tick_input,
+            &mut physics_state,
+            &mut time_stack,
+            &mut stats_opt,
+            &mut ghost_state,
+            &mut state_stack,
+        );
+
+        clock.accumulator -= TICK_DT;
+        ticks += 1;
+    }
+
+    clock.alpha = clock.accumulator / TICK_DT;
+}
+
+/// Returns true if the state stack is in a state advanced by ticks
+pub fn is_running(state_stack: &StateStack) -> bool {
+    state_stack.get::<states::RewindTime>().is_some() ||
+        state_stack.get::<states::RunLevel>().is_some()
+}
+
+/// Advances the game by one tick; independent of the renderer, so that it can
+/// be driven both by the frame loop and by the headless runner
+pub fn tick(
+    world: &World,
+    input: &actions::TickInput,
+    physics_state: &mut physics::State,
+    time_stack: &mut time::Stack,
+    stats_opt: &mut Option<states::Stats>,
+    ghost_state: &mut ghost::State,
+    state_stack: &mut StateStack,
+) {
+    if state_stack.get::<states::RewindTime>().is_some() {
+        rewind_tick(world, input, physics_state, time_stack, stats_opt, ghost_state, state_stack);
+    } else if state_stack.get::<states::RunLevel>().is_some() {
+        run_tick(world, input, physics_state, time_stack, stats_opt, ghost_state, state_stack);
+    }
+}
+
+fn run_tick(
+    world: &World,
+    input: &actions::TickInput,
+    physics_state: &mut physics::State,
+    time_stack: &mut time::Stack,
+    stats_opt: &mut Option<states::Stats>,
+    ghost_state: &mut ghost::State,
+    state_stack: &mut StateStack,
+) {
+    if let Some(rewound) = time::switch_branch(world, time_stack, physics_state, stats_opt, input) {
+        ghost::rewind(ghost_state, rewound, true);
+    }
+    time::replay(world, time_stack);
+    time::select_object(world, input);
+    time::slow_down(world, time_stack, physics_state, stats_opt, input);
+    player::control(world, input, physics_state, time_stack);
+    past_self::control(world, physics_state);
+    signal::control(world, physics_state);
+    trampoline::control(world, input, physics_state, time_stack);
+    platform::control(world, physics_state);
+    door::control(world, physics_state);
+    light::control(world);
+    states::update(stats_opt, physics_state, world, state_stack);
+    time_capsule::control(world, physics_state, time_stack);
+
+    if let Some(stats) = stats_opt.as_ref() {
+        checkpoint::control(world, physics_state, stats.time);
+    }
+    states::check_fall(stats_opt, physics_state, time_stack, world, state_stack);
+
+    time::update_stacks(world, time_stack, physics_state, stats_opt, input, state_stack);
+
+    // the level may have been passed or a rewind started during this tick
+    if state_stack.get::<states::RunLevel>().is_some() {
+        ghost::record(world, physics_state, ghost_state);
+        physics::step(physics_state);
+    }
+}
+
+fn rewind_tick(
+    world: &World,
+    input: &actions::TickInput,
+    physics_state: &mut physics::State,
+    time_stack: &mut time::Stack,
+    stats_opt: &mut Option<states::Stats>,
+    ghost_state: &mut ghost::State,
+    state_stack: &mut StateStack,
+) {
+    time::select_object(world, input);
+    let rewound = time::rewind(world, time_stack, physics_state, stats_opt, input, state_stack);
+    past_self::update_visibility(world);
+    light::control(world);
+    states::update(stats_opt, physics_state, world, state_stack);
+    time_capsule::control(world, physics_state, time_stack);
+
+    let finished = state_stack.get::<states::RewindTime>().is_none();
+    ghost::rewind(ghost_state, rewound, finished);
+}
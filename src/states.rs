@@ -1,25 +1,48 @@
 use dotrix::{
-    State, World, Transform,
-    ecs::{ Mut, Const, },
+    State, World,
+    ecs::{ Mut, },
     math::{ Vec3, },
 };
 
+use crate::checkpoint;
+use crate::level;
 use crate::physics;
 use crate::player;
+use crate::simulation;
+use crate::time;
+
+/// Length of the automatic rewind after a fall
+const AUTO_REWIND_TICKS: usize = 2 * simulation::TICK_RATE as usize;
 
 // Services
 pub struct Stats {
+    pub level_name: String,
     pub time: f32,
     pub level_passed: bool,
+    pub new_record: bool,
+    pub recorded: bool,
     pub finish_pos: Vec3,
+    pub kill_height: Option<f32>,
+    pub slow_time: time::SlowTime,
+    pub falls: u32,
 }
 
 impl Stats {
-    pub fn new(finish_pos: Vec3) -> Self {
+    pub fn new(level: &level::Level) -> Self {
         Self {
+            level_name: level.name.clone(),
             time: 0.0,
             level_passed: false,
-            finish_pos,
+            new_record: false,
+            recorded: false,
+            finish_pos: Vec3::new(
+                level.target_position.0,
+                level.target_position.1,
+                level.target_position.2
+            ),
+            kill_height: level.kill_height,
+            slow_time: level.slow_time,
+            falls: 0,
         }
     }
 }
@@ -59,7 +82,26 @@ pub struct InitLevel {}
 
 pub struct RunLevel {}
 
-pub struct RewindTime {}
+pub struct RewindTime {
+    /// Ticks left to rewind without the rewind action being held, if started automatically
+    pub auto_ticks: Option<usize>,
+    /// Ticks rewound per tick while the rewind action is held
+    pub speed: usize,
+    /// The time is paused and can be moved back and forth until committed
+    pub scrubbing: bool,
+}
+
+impl Default for RewindTime {
+    fn default() -> Self {
+        Self {
+            auto_ticks: None,
+            speed: 1,
+            scrubbing: false,
+        }
+    }
+}
+
+pub struct Restart {}
 
 // Systems
 pub fn after_init(
@@ -69,29 +111,77 @@ pub fn after_init(
 }
 
 pub fn update (
-    mut stats_opt: Mut<Option<Stats>>,
-    physics_state: Const<physics::State>,
-    world: Const<World>,
-    mut state: Mut<State>,
+    stats_opt: &mut Option<Stats>,
+    physics_state: &physics::State,
+    world: &World,
+    state: &mut State,
 ) {
     let mut stats = stats_opt.as_mut()
         .expect("Game stats should be initialized");
 
-    stats.time += physics_state.physics.as_ref()
-        .expect("physics_state must be defined").integration_parameters.dt;
-
-    let query = world.query::<(
-        &Transform, &player::State,
-    )>();
-
-    for (transform, _) in query {
-        if (
-            (transform.translate.x - stats.finish_pos.x).powi(2) +
-            (transform.translate.y - stats.finish_pos.y).powi(2) +
-            (transform.translate.z - stats.finish_pos.z).powi(2)
-        ).sqrt() <= 2.0 {
-            stats.level_passed = true;
-            state.push(Pause::default());
+    // while rewinding the time is restored from the time stack, slow motion
+    // does not slow down the run time
+    if state.get::<RewindTime>().is_none() {
+        stats.time += simulation::TICK_DT;
+    }
+
+    if let Some(player_collider) = player::collider(world) {
+        let query = world.query::<(&level::Target, &physics::Sensor,)>();
+
+        for (_, sensor) in query {
+            if physics_state.intersection_started(player_collider, sensor.collider) {
+                stats.level_passed = true;
+                state.push(Pause::default());
+                break;
+            }
         }
     }
 }
+
+/// Handles the player falling below the kill height: respawns at the last
+/// checkpoint, otherwise rewinds automatically if there is hourglass time left,
+/// otherwise restarts the level
+pub fn check_fall (
+    stats_opt: &mut Option<Stats>,
+    physics_state: &mut physics::State,
+    time_stack: &time::Stack,
+    world: &World,
+    state: &mut State,
+) {
+    let stats = stats_opt.as_mut()
+        .expect("Game stats should be initialized");
+
+    let kill_height = match stats.kill_height {
+        Some(kill_height) => kill_height,
+        None => return,
+    };
+
+    let fell = player::pose(world, physics_state)
+        .map(|pose| pose.translation.y < kill_height)
+        .unwrap_or(false);
+
+    if !fell {
+        return;
+    }
+
+    stats.falls += 1;
+
+    if let Some(position) = checkpoint::last_position(world) {
+        checkpoint::respawn(world, physics_state, position);
+    } else if time_stack.budget() > 0 {
+        state.push(RewindTime {
+            auto_ticks: Some(AUTO_REWIND_TICKS),
+            ..Default::default()
+        });
+    } else {
+        state.push(Restart {});
+    }
+}
+
+pub fn restart (
+    mut state: Mut<State>,
+) {
+    while !state.get::<MainMenu>().is_some() {
+        state.pop_any();
+    }
+}
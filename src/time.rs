@@ -1,39 +1,224 @@
 use dotrix::{
-    Input,
     World,
-    ecs::{ Mut, Const, },
     State as StateStack,
 };
 
+use crate::checkpoint;
+use crate::door;
 use crate::physics;
+use crate::past_self;
+use crate::platform;
 use crate::player;
-use crate::actions::Action;
+use crate::signal;
+use crate::trampoline;
+use crate::time_capsule;
+use crate::actions::{ Action, TickInput, };
+use crate::simulation;
 use crate::states;
+use crate::timeline::{ Cursor, Timeline, };
 
-use std::collections::VecDeque;
+use serde::{Serialize, Deserialize};
+use std::sync::{ Arc, Mutex, };
 
-pub const STACK_MAX_SIZE: usize = 900;
+/// Rewind window in ticks used when the level does not set one (15 seconds
+/// at the simulation tick rate)
+pub const DEFAULT_STACK_SIZE: usize = 900;
 
-pub struct Stack {
-    pub physics_state: VecDeque<Option<physics::PhysicsState>>,
-    pub count: usize,
-    pub index: usize,
-    pub index_cleared: usize,
-    pub index_max: usize,
-    pub di: usize,
+/// The full physics state is kept at least every this many ticks, the ticks
+/// in between only keep the moving bodies
+pub const KEYFRAME_INTERVAL: usize = 60;
+
+/// Fastest rewind speed, in ticks rewound per tick
+const MAX_REWIND_SPEED: usize = 4;
+
+/// Slow motion settings of a level
+#[derive(Serialize, Deserialize, Clone, Copy, std::fmt::Debug)]
+#[serde(default)]
+pub struct SlowTime {
+    /// Physics time step multiplier while the time is slowed
+    pub scale: f32,
+    /// Rewind ticks consumed per slowed tick
+    pub cost: f32,
 }
 
-impl Default for Stack {
+impl Default for SlowTime {
     fn default() -> Self {
         Self {
-            physics_state: VecDeque::new(),
-            count: 0,
-            index: 0,
-            index_cleared: 0,
-            index_max: 0,
-            di: 0
+            scale: 0.5,
+            cost: 1.0,
+        }
+    }
+}
+
+/// Rewind history shared by the level, the cursor is the position of the
+/// time in the recorded ticks and is replayed from
+pub struct Stack {
+    physics_frames: Timeline<physics::Frame>,
+    stats_time: Timeline<f32>,
+    /// Ticks run in slow motion, shown on the clock
+    slowed: Timeline<bool>,
+    /// Whether the current tick runs in slow motion
+    slowing: bool,
+    /// Fraction of a rewind tick consumed by slow motion but not yet taken
+    slow_cost: f32,
+    cursor: Cursor,
+    /// Timeline discarded when the player changed the past
+    branch: Option<Branch>,
+    /// Tick the branch and the current timeline split at, counted from the
+    /// level start
+    branch_tick: usize,
+}
+
+/// Recorded timeline kept to switch back to, with the cursor at the tick it
+/// split from the current one
+struct Branch {
+    physics_frames: Timeline<physics::Frame>,
+    stats_time: Timeline<f32>,
+    slowed: Timeline<bool>,
+    cursor: Cursor,
+}
+
+impl Stack {
+    /// Creates a stack for the given rewind window and starting budget in ticks
+    pub fn new(max_size: usize, budget: usize) -> Self {
+        Self {
+            physics_frames: Timeline::new(max_size),
+            stats_time: Timeline::new(max_size),
+            slowed: Timeline::new(max_size),
+            slowing: false,
+            slow_cost: 0.0,
+            cursor: Cursor::new(max_size, budget),
+            branch: None,
+            branch_tick: 0,
         }
     }
+
+    /// Rewind window in ticks
+    pub fn capacity(&self) -> usize {
+        self.cursor.capacity()
+    }
+
+    pub fn cursor(&self) -> &Cursor {
+        &self.cursor
+    }
+
+    /// Rewind ticks left
+    pub fn budget(&self) -> usize {
+        self.cursor.budget()
+    }
+
+    /// Adds rewind ticks, up to the rewind window
+    pub fn add_budget(&mut self, ticks: usize) {
+        self.cursor.add_budget(ticks);
+    }
+
+    /// Takes rewind ticks back, down to zero
+    pub fn spend_budget(&mut self, ticks: usize) {
+        self.cursor.spend_budget(ticks);
+    }
+
+    /// Ticks shown on the clock since the last cleared tick
+    pub fn elapsed(&self) -> usize {
+        self.cursor.elapsed()
+    }
+
+    /// Ticks the time is currently rewound by
+    pub fn rewound(&self) -> usize {
+        self.cursor.rewound()
+    }
+
+    /// Ticks that can be rewound from the clock start
+    pub fn available(&self) -> usize {
+        self.cursor.available()
+    }
+
+    /// Ages of the ticks run in slow motion
+    pub fn slowed_ticks(&self) -> impl Iterator<Item = usize> + '_ {
+        self.slowed.iter()
+            .enumerate()
+            .filter(|(_, slowed)| *slowed == Some(&true))
+            .map(|(age, _)| age)
+    }
+
+    /// Whether the cursor can be moved one tick to the past, the ticks older
+    /// than the oldest kept keyframe cannot be rebuilt
+    fn can_step_back(&self) -> bool {
+        let age = self.cursor.age().map_or(0, |age| age + 1);
+
+        !self.cursor.at_rewind_limit() &&
+            nearest_keyframe(&self.physics_frames, age).is_some()
+    }
+
+    /// Rewind ticks paid for switching to the branch: the current timeline
+    /// is rewound to the tick the branches split at
+    fn branch_cost(&self) -> usize {
+        self.cursor.tick().saturating_sub(self.branch_tick)
+    }
+
+    /// Whether there is a branch and the rewind budget to switch to it
+    pub fn can_switch_branch(&self) -> bool {
+        self.branch.is_some() && self.branch_cost() <= self.cursor.budget()
+    }
+
+    /// Keeps the recorded timeline as a branch splitting at the cursor, must
+    /// be called before the rewound ticks are cleared
+    fn keep_branch(&mut self) {
+        self.branch = Some(Branch {
+            physics_frames: self.physics_frames.clone(),
+            stats_time: self.stats_time.clone(),
+            slowed: self.slowed.clone(),
+            cursor: self.cursor,
+        });
+        self.branch_tick = self.cursor.tick();
+    }
+
+    /// Swaps the timelines with the branch and moves the cursor to the tick
+    /// they split at, paying for it like for a rewind; the current timeline
+    /// is kept as the branch if that tick is still recorded. Returns the
+    /// ticks the current timeline is rewound by, or None if there is no
+    /// branch or not enough budget
+    fn switch_branch(&mut self) -> Option<usize> {
+        if !self.can_switch_branch() {
+            return None;
+        }
+
+        let rewound = self.branch_cost();
+        let mut branch = self.branch.take()?;
+
+        std::mem::swap(&mut self.physics_frames, &mut branch.physics_frames);
+        std::mem::swap(&mut self.stats_time, &mut branch.stats_time);
+        std::mem::swap(&mut self.slowed, &mut branch.slowed);
+        let split = self.cursor.at_tick(self.branch_tick)
+            .filter(|split| split.age()
+                .and_then(|age| nearest_keyframe(&branch.physics_frames, age))
+                .is_some()
+            );
+
+        self.cursor.move_to(&branch.cursor);
+        self.cursor.spend_budget(rewound);
+
+        if let Some(split) = split {
+            branch.cursor = split;
+            self.branch = Some(branch);
+        }
+
+        Some(rewound)
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new(DEFAULT_STACK_SIZE, 0)
+    }
+}
+
+/// Input given to an actionable object in a tick, replayed after a rewind
+#[derive(Debug, Clone)]
+pub enum ObjectAction {
+    /// Torques applied to the player ball
+    Roll(player::Action),
+    /// The object was used, e.g. a trampoline threw the ball up
+    Use,
 }
 
 #[derive(Debug)]
@@ -42,103 +227,574 @@ pub struct ActionableObject {
     pub selected: bool,
     pub is_player: bool,
     pub tile_texture_name: &'static str,
+    /// Action of the current tick, either replayed or given by the player
+    pub current_action: Option<ObjectAction>,
+    action_stack: Timeline<ObjectAction>,
+    /// Action stack of the timeline branch, see `Stack::keep_branch`
+    action_branch: Option<Timeline<ObjectAction>>,
 }
 
-pub fn rewind (
-    mut stack: Mut<Stack>,
-    mut physics_state: Mut<physics::State>,
-    input: Const<Input>,
-    mut state_stack: Mut<StateStack>,
+impl ActionableObject {
+    /// Creates an object, the player is active and selected from the start
+    pub fn new(tile_texture_name: &'static str, is_player: bool) -> Self {
+        Self {
+            active: is_player,
+            selected: is_player,
+            is_player,
+            tile_texture_name,
+            current_action: None,
+            action_stack: Timeline::default(),
+            action_branch: None,
+        }
+    }
+
+    fn record_action(&mut self, cursor: &Cursor) {
+        self.action_stack.set_capacity(cursor.capacity());
+        self.action_stack.record(self.current_action.take(), cursor);
+    }
+
+    fn replay_action(&mut self, cursor: &Cursor) {
+        self.current_action = self.action_stack.at(cursor).cloned();
+    }
+
+    /// Clears the actions of the given ages, returns them in the order they
+    /// would have been replayed
+    fn clear_actions(&mut self, ages: std::ops::Range<usize>) -> Vec<Option<ObjectAction>> {
+        ages.rev()
+            .map(|age| self.action_stack.take(age))
+            .collect()
+    }
+
+    fn keep_branch(&mut self) {
+        self.action_branch = Some(self.action_stack.clone());
+    }
+
+    fn switch_branch(&mut self) {
+        if let Some(branch) = self.action_branch.as_mut() {
+            std::mem::swap(&mut self.action_stack, branch);
+        }
+    }
+}
+
+impl Rewindable for ActionableObject {
+    type Snapshot = bool;
+
+    fn snapshot(&self) -> bool {
+        self.active
+    }
+
+    fn restore(&mut self, active: &bool) {
+        self.active = *active;
+    }
+}
+
+/// Rigid body that keeps its state when the time is rewound
+pub struct Echo {
+    pub enabled: bool,
+}
+
+/// Component state that is captured every frame and restored on rewind
+pub trait Rewindable {
+    type Snapshot: Clone;
+
+    fn snapshot(&self) -> Self::Snapshot;
+    fn restore(&mut self, snapshot: &Self::Snapshot);
+}
+
+/// Per-entity history of a rewindable component, spawned alongside it
+pub struct History<T: Rewindable> {
+    timeline: Timeline<T::Snapshot>,
+    /// Timeline of the branch, see `Stack::keep_branch`
+    branch: Option<Timeline<T::Snapshot>>,
+    /// Nothing is recorded, the component keeps its state when the time is
+    /// rewound, see `Echo`
+    echo: bool,
+}
+
+impl<T: Rewindable> Default for History<T> {
+    fn default() -> Self {
+        Self {
+            timeline: Timeline::default(),
+            branch: None,
+            echo: false,
+        }
+    }
+}
+
+impl<T: Rewindable> History<T> {
+    /// Creates the history of an echo object
+    pub fn echo() -> Self {
+        Self {
+            echo: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn record(&mut self, object: &T, cursor: &Cursor) {
+        if self.echo {
+            return;
+        }
+
+        self.timeline.set_capacity(cursor.capacity());
+        self.timeline.record(Some(object.snapshot()), cursor);
+    }
+
+    pub fn restore(&self, object: &mut T, cursor: &Cursor) {
+        if let Some(snapshot) = self.timeline.at(cursor) {
+            object.restore(snapshot);
+        }
+    }
+
+    fn keep_branch(&mut self) {
+        self.branch = Some(self.timeline.clone());
+    }
+
+    fn switch_branch(&mut self) {
+        if let Some(branch) = self.branch.as_mut() {
+            std::mem::swap(&mut self.timeline, branch);
+        }
+    }
+}
+
+/// Runs the expression for every rewindable component and its history, this
+/// is the only list of the rewindable component types
+macro_rules! for_each_history {
+    ($world:expr, |$component:ident, $history:ident| $body:expr) => {
+        for_each_history!(@types $world, $component, $history, $body;
+            ActionableObject,
+            Arc<Mutex<trampoline::State>>,
+            time_capsule::State,
+            checkpoint::State,
+            platform::State,
+            past_self::State,
+            door::State,
+            signal::Switch,
+            signal::Receiver
+        )
+    };
+    (@types $world:expr, $component:ident, $history:ident, $body:expr; $($type:ty),*) => {
+        $(
+            let query = $world.query::<(&mut $type, &mut History<$type>,)>();
+            for ($component, $history) in query {
+                $body;
+            }
+        )*
+    };
+}
+
+/// Switches the selected actionable object, falling back to the player when
+/// the selected object is no longer active
+pub fn select_object(
+    world: &World,
+    input: &TickInput,
 ) {
-    stack.index += 1;
-    stack.di += 1;
+    let reversed = input.is_action_activated(Action::SelectActiveObjectLeft);
+    let select = reversed ||
+        input.is_action_activated(Action::SelectActiveObjectRight);
+
+    let query = world.query::<(&mut ActionableObject,)>();
+    let mut objects = query.map(|(object,)| object).collect::<Vec<_>>();
+
+    let count = objects.len();
+    let player = objects.iter().position(|object| object.is_player);
+
+    let next = match objects.iter().position(|object| object.selected) {
+        Some(i) if !objects[i].active => player,
+        Some(i) if select => (1..count)
+            .map(|k| if reversed { (i + count - k) % count } else { (i + k) % count })
+            .find(|&j| objects[j].active)
+            .or(player),
+        None => player,
+        _ => return,
+    };
+
+    if let Some(next) = next {
+        for object in objects.iter_mut() {
+            object.selected = false;
+        }
+        objects[next].selected = true;
+    }
+}
+
+/// Moves the time back while the rewind action is held, or back and forth
+/// while scrubbing; returns the ticks rewound by the current rewind
+pub fn rewind (
+    world: &World,
+    stack: &mut Stack,
+    physics_state: &mut physics::State,
+    stats_opt: &mut Option<states::Stats>,
+    input: &TickInput,
+    state_stack: &mut StateStack,
+) -> usize {
+    let mut auto_rewind = false;
+    let mut speed = 1;
+    let mut scrubbing = false;
+
+    if let Some(rewind_state) = state_stack.get_mut::<states::RewindTime>() {
+        if let Some(auto_ticks) = rewind_state.auto_ticks.as_mut() {
+            if *auto_ticks > 0 {
+                *auto_ticks -= 1;
+                auto_rewind = true;
+            }
+        }
+
+        if !auto_rewind {
+            if input.is_action_activated(Action::RewindFaster) {
+                rewind_state.speed = (rewind_state.speed * 2).min(MAX_REWIND_SPEED);
+            }
+            if input.is_action_activated(Action::RewindSlower) {
+                rewind_state.speed = (rewind_state.speed / 2).max(1);
+            }
+            // toggling the scrubbing off commits the scrub position
+            if input.is_action_activated(Action::ScrubTime) {
+                rewind_state.scrubbing = !rewind_state.scrubbing;
+            }
+
+            speed = rewind_state.speed;
+            scrubbing = rewind_state.scrubbing;
+        }
+    }
 
-    println!("{:?} {:?} {:?}", stack.index, stack.physics_state.len(), stack.index_max);
-    physics_state.physics = stack.physics_state[stack.index-1].clone();
+    // the first tick of a rewind always steps back to have a tick to restore
+    let steps_back = if stack.cursor.rewinding() == 0 || auto_rewind {
+        1
+    } else if input.is_action_hold(Action::RewindTime) {
+        speed
+    } else {
+        0
+    };
 
-    if input.is_action_deactivated(Action::RewindTime) ||
-        !input.is_action_hold(Action::RewindTime) ||
-        (stack.index >= stack.physics_state.len()) ||
-        (stack.di >= stack.index_max)
+    for _ in 0..steps_back {
+        if !stack.can_step_back() {
+            break;
+        }
+        stack.cursor.step_back();
+    }
+
+    if scrubbing && input.is_action_hold(Action::ScrubForward) &&
+        !input.is_action_hold(Action::RewindTime)
     {
-        stack.index_max -= stack.di;
-        stack.di = 0;
-        state_stack.pop::<states::RewindTime>().expect("Expected RewindTime state");
+        for _ in 0..speed {
+            if !stack.cursor.scrub_forward() {
+                break;
+            }
+        }
+    }
+
+    restore_tick(world, stack, physics_state, stats_opt);
+
+    let rewound = stack.cursor.rewinding();
+
+    if !scrubbing && (
+        (!auto_rewind && !input.is_action_hold(Action::RewindTime)) ||
+        !stack.can_step_back()
+    ) {
+        stack.cursor.finish_rewind();
+        let rewind_state = state_stack.pop::<states::RewindTime>()
+            .expect("Expected RewindTime state");
+
+        // do not replay the actions which led to the fall
+        if rewind_state.auto_ticks.is_some() {
+            clear_rewound_actions(world, stack);
+        }
     }
+
+    rewound
+}
+
+/// Restores the tick at the cursor
+fn restore_tick (
+    world: &World,
+    stack: &Stack,
+    physics_state: &mut physics::State,
+    stats_opt: &mut Option<states::Stats>,
+) {
+    let echoes = echo_bodies(world, physics_state);
+    if let (Some(frame), Some(keyframe)) = (
+        stack.physics_frames.at(&stack.cursor),
+        stack.cursor.age().and_then(|age| nearest_keyframe(&stack.physics_frames, age)),
+    ) {
+        physics_state.restore_frame(frame, keyframe);
+    }
+    restore_echo_bodies(physics_state, echoes);
+    physics_state.snap_previous_positions();
+
+    if let (Some(stats), Some(time)) = (
+        stats_opt.as_mut(),
+        stack.stats_time.at(&stack.cursor)
+    ) {
+        stats.time = *time;
+    }
+
+    restore_components(world, &stack.cursor);
 }
 
 pub fn replay (
-    world: Const<World>,
-    mut stack: Mut<Stack>,
+    world: &World,
+    stack: &mut Stack,
 ) {
-    if stack.index > 0 {
-        stack.index -= 1;
-        println!("{:?} {:?} {:?}", stack.index, stack.physics_state.len(), stack.index_max);
-
-        // player
-        if stack.index > 0 {
-            let query = world.query::<(&mut player::State,)>();
-            for (state_player,) in query {
-                state_player.current_action =
-                    state_player.action_stack[stack.index-1].clone();
-            }
+    let replaying = stack.cursor.age().is_some();
+
+    stack.cursor.step_forward();
+
+    if replaying && stack.cursor.age().is_some() {
+        let query = world.query::<(&mut ActionableObject,)>();
+        for (object,) in query {
+            object.replay_action(&stack.cursor);
         }
-    } else {
-        stack.count += 1;
     }
+}
 
-    if stack.index_cleared > stack.index {
-        stack.index_cleared = stack.index;
+/// Keeps the action stacks and the component histories as the branch, see
+/// `Stack::keep_branch`
+fn keep_branch(world: &World) {
+    let query = world.query::<(&mut ActionableObject,)>();
+    for (object,) in query {
+        object.keep_branch();
     }
+
+    for_each_history!(world, |_component, history| history.keep_branch());
+}
+
+/// Called when an action is given while the time is replayed: the rewound
+/// future is kept as a branch, its actions are no longer replayed and the
+/// discarded player actions are replayed by the past self instead
+pub fn change_past(
+    world: &World,
+    stack: &mut Stack,
+    physics_state: &mut physics::State,
+) {
+    if stack.cursor.rewound() == 0 {
+        return;
+    }
+
+    stack.keep_branch();
+    keep_branch(world);
+
+    let discarded = clear_rewound_actions(world, stack);
+
+    // the discarded timeline is replayed by the past self, if the level has one
+    if discarded.iter().any(|action| action.is_some()) {
+        past_self::activate(world, physics_state, discarded);
+    }
+}
+
+/// Clears the actions of the rewound ticks not yet replayed, returns the
+/// player actions in the order they would have been replayed
+fn clear_rewound_actions(
+    world: &World,
+    stack: &mut Stack,
+) -> Vec<Option<player::Action>> {
+    let ages = stack.cursor.clear_rewound();
+    let mut discarded = Vec::new();
+
+    let query = world.query::<(&mut ActionableObject,)>();
+    for (object,) in query {
+        let actions = object.clear_actions(ages.clone());
+
+        if object.is_player {
+            discarded = actions.into_iter()
+                .map(|action| match action {
+                    Some(ObjectAction::Roll(action)) => Some(action),
+                    _ => None,
+                })
+                .collect();
+        }
+    }
+
+    discarded
+}
+
+/// Switches to the kept branch when requested, the time continues from the
+/// tick the branches split at; returns the ticks the switched away timeline
+/// is rewound by
+pub fn switch_branch(
+    world: &World,
+    stack: &mut Stack,
+    physics_state: &mut physics::State,
+    stats_opt: &mut Option<states::Stats>,
+    input: &TickInput,
+) -> Option<usize> {
+    if !input.is_action_activated(Action::SwitchBranch) {
+        return None;
+    }
+
+    let rewound = stack.switch_branch()?;
+
+    switch_component_branches(world);
+
+    restore_tick(world, stack, physics_state, stats_opt);
+
+    Some(rewound)
+}
+
+/// Scales the physics time step while the slow motion is held, as long as
+/// there is rewind time left to pay for it; replayed ticks run as recorded
+pub fn slow_down (
+    world: &World,
+    stack: &mut Stack,
+    physics_state: &mut physics::State,
+    stats_opt: &Option<states::Stats>,
+    input: &TickInput,
+) {
+    let slow_time = stats_opt.as_ref()
+        .map(|stats| stats.slow_time)
+        .unwrap_or_default();
+
+    let held = input.is_action_hold(Action::SlowTime) && stack.cursor.budget() > 0;
+
+    // replayed slow motion was paid for when it was recorded
+    let replaying = stack.cursor.is_replaying();
+    let replayed = replaying && stack.slowed.at(&stack.cursor) == Some(&true);
+
+    // slowing down a tick replayed at the full speed changes the past
+    if held && !replayed && replaying {
+        change_past(world, stack, physics_state);
+    }
+
+    stack.slowing = held || replayed;
+
+    let dt = if stack.slowing {
+        if !replayed {
+            stack.slow_cost += slow_time.cost;
+            while stack.slow_cost >= 1.0 {
+                stack.cursor.spend_budget(1);
+                stack.slow_cost -= 1.0;
+            }
+        }
+
+        simulation::TICK_DT * slow_time.scale
+    } else {
+        simulation::TICK_DT
+    };
+
+    physics_state.physics.as_mut().expect("physics::State must be defined")
+        .integration_parameters.dt = dt;
 }
 
 pub fn update_stacks (
-    world: Const<World>,
-    mut stack: Mut<Stack>,
-    physics_state: Const<physics::State>,
-    input: Const<Input>,
-    mut state_stack: Mut<StateStack>,
+    world: &World,
+    stack: &mut Stack,
+    physics_state: &physics::State,
+    stats_opt: &Option<states::Stats>,
+    input: &TickInput,
+    state_stack: &mut StateStack,
 ) {
-    let index = stack.index;
+    let cursor = stack.cursor;
+
+    // physics engine, a keyframe is kept if there is none among the previous ticks
+    let keyframe = !stack.physics_frames.before(&cursor)
+        .take(KEYFRAME_INTERVAL - 1)
+        .any(|frame| frame.and_then(|frame| frame.keyframe()).is_some());
 
-    stack.index_max = stack.index_max.min(STACK_MAX_SIZE);
+    let frame = physics_state.frame(keyframe);
+    stack.physics_frames.record(Some(frame), &cursor);
 
-    // physics engine
-    update_stack(
-        &mut stack.physics_state,
-        physics_state.physics.clone(),
-        index
-    );
+    // game stats
+    stack.stats_time.record(stats_opt.as_ref().map(|stats| stats.time), &cursor);
 
-    // player
-    let query = world.query::<(&mut player::State,)>();
-    for (state_player,) in query {
-        update_stack(
-            &mut state_player.action_stack,
-            state_player.current_action.take(),
-            index
-        );
+    // slow motion
+    let slowing = stack.slowing;
+    stack.slowed.record(Some(slowing), &cursor);
+
+    record_components(world, &cursor);
+
+    // actions
+    let query = world.query::<(&mut ActionableObject,)>();
+    for (object,) in query {
+        object.record_action(&cursor);
     }
 
     if input.is_action_activated(Action::RewindTime) &&
-        (stack.index_max > 0)
+        (cursor.budget() > 0)
+    {
+        state_stack.push(states::RewindTime::default());
+    } else if input.is_action_activated(Action::ScrubTime) &&
+        (cursor.budget() > 0)
     {
-        state_stack.push(states::RewindTime {});
+        state_stack.push(states::RewindTime {
+            scrubbing: true,
+            ..Default::default()
+        });
     }
 }
 
-fn update_stack<T> (
-    stack: &mut VecDeque<Option<T>>,
-    new_element: Option<T>,
-    index: usize,
-) {
-    if index == 0 {
-        stack.push_front(new_element);
-    } else {
-        stack[index-1] = new_element;
+fn record_components(world: &World, cursor: &Cursor) {
+    for_each_history!(world, |component, history| history.record(component, cursor));
+}
+
+fn restore_components(world: &World, cursor: &Cursor) {
+    for_each_history!(world, |component, history| history.restore(component, cursor));
+}
+
+/// Swaps the action stacks and the component histories with the branch
+fn switch_component_branches(world: &World) {
+    let query = world.query::<(&mut ActionableObject,)>();
+    for (object,) in query {
+        object.switch_branch();
     }
 
-    while stack.len() > STACK_MAX_SIZE {
-        stack.pop_back();
+    for_each_history!(world, |_component, history| history.switch_branch());
+}
+
+/// Returns the newest full physics state at or before the tick of the given
+/// age, a frame is rebuilt on top of it
+fn nearest_keyframe(
+    frames: &Timeline<physics::Frame>,
+    age: usize,
+) -> Option<&physics::PhysicsState> {
+    (age..frames.len()).find_map(|age| frames.get(age)
+        .and_then(|frame| frame.keyframe())
+    )
+}
+
+/// Body state of an echo object kept over the physics restore
+struct EchoBody {
+    rigid_body: physics::RigidBodyHandle,
+    position: physics::Isometry<physics::Real>,
+    linvel: physics::Vector<physics::Real>,
+    angvel: physics::Vector<physics::Real>,
+}
+
+fn echo_bodies(
+    world: &World,
+    physics_state: &physics::State,
+) -> Vec<EchoBody> {
+    let rigid_body_set = &physics_state.physics.as_ref()
+        .expect("physics::State must be defined")
+        .rigid_body_set;
+
+    let query = world.query::<(&physics::RigidBodyHandle, &Echo,)>();
+
+    query.filter(|(_, echo)| echo.enabled)
+        .filter_map(|(rigid_body, _)| rigid_body_set.get(*rigid_body)
+            .map(|body| EchoBody {
+                rigid_body: *rigid_body,
+                position: *body.position(),
+                linvel: *body.linvel(),
+                angvel: *body.angvel(),
+            })
+        )
+        .collect()
+}
+
+fn restore_echo_bodies(
+    physics_state: &mut physics::State,
+    echoes: Vec<EchoBody>,
+) {
+    let rigid_body_set = &mut physics_state.physics.as_mut()
+        .expect("physics::State must be defined")
+        .rigid_body_set;
+
+    for echo in echoes {
+        if let Some(body) = rigid_body_set.get_mut(echo.rigid_body) {
+            body.set_position(echo.position, true);
+            if body.is_kinematic() {
+                body.set_next_kinematic_position(echo.position);
+            } else {
+                body.set_linvel(echo.linvel, true);
+                body.set_angvel(echo.angvel, true);
+            }
+        }
     }
 }
@@ -1,73 +1,180 @@
 use dotrix::{
-    Assets, World, Transform, Id,
+    Assets, World, Transform, Id, Color,
     assets::Mesh,
     pbr::{ Model, Material, },
-    ecs::{ Mut, Entity, },
     math::{ Vec3, Quat, InnerSpace, },
     renderer::Render,
 };
 
+use crate::physics;
 use crate::player;
 use crate::time;
 
+use serde::{Serialize, Deserialize};
+
 const SCALE: f32 = 0.4;
-const MIN_DIST: f32 = 0.75;
+/// Distance of the ball center to collect the capsule, the sensor triggers
+/// once the ball touches it so it is just over the ball radius
+const MIN_DIST: f32 = 1.1;
+
+pub const DEFAULT_MODEL: &str = "time_capsule";
+
+/// What happens when a capsule would fill the hourglass over its capacity
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, std::fmt::Debug)]
+pub enum Overflow {
+    /// The capsule can not be collected until all of its time fits
+    Refuse,
+    /// The capsule is collected unless the hourglass is full, the time over
+    /// the capacity is lost
+    Clamp,
+}
+
+impl Default for Overflow {
+    fn default() -> Self {
+        Overflow::Clamp
+    }
+}
+
+/// Tunable capsule behaviour
+pub struct Config {
+    /// Rewind ticks added to the hourglass, half of the rewind window by default
+    pub amount: Option<usize>,
+    /// Seconds until a collected capsule appears again
+    pub respawn_delay: Option<f32>,
+    pub overflow: Overflow,
+    /// Model in the assets folder
+    pub model: String,
+    pub color: Option<Color>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            amount: None,
+            respawn_delay: None,
+            overflow: Overflow::default(),
+            model: DEFAULT_MODEL.to_string(),
+            color: None,
+        }
+    }
+}
 
 pub struct State {
     position: Vec3,
+    config: Config,
+    collected: bool,
+    respawn_left: Option<f32>,
+    /// Rewind ticks the capsule has put into the hourglass
+    added: usize,
+    /// Rewind ticks to put into or take out of the hourglass because a
+    /// restore changed `added`
+    budget_change: isize,
+}
+
+impl time::Rewindable for State {
+    type Snapshot = (bool, Option<f32>, usize);
+
+    fn snapshot(&self) -> (bool, Option<f32>, usize) {
+        (self.collected, self.respawn_left, self.added)
+    }
+
+    fn restore(&mut self, snapshot: &(bool, Option<f32>, usize)) {
+        self.collected = snapshot.0;
+        self.respawn_left = snapshot.1;
+        self.budget_change += snapshot.2 as isize - self.added as isize;
+        self.added = snapshot.2;
+    }
 }
 
 pub fn load_assets(
     assets: &mut Assets,
+    model: &str,
 ) -> Id<Mesh> {
-    assets.import("assets/time_capsule.gltf");
-    assets.register("time_capsule::mesh")
+    assets.import(&["assets/", model, ".gltf"].join(""));
+    assets.register(&[model, "mesh"].join("::"))
 }
 
 pub fn spawn(
     world: &mut World,
     assets: &mut Assets,
+    physics_state: &mut physics::State,
     position: Vec3,
+    config: Config,
 ) {
-    let texture = assets.register("time_capsule::texture");
-    let mesh = assets.register("time_capsule::mesh");
+    let sensor = physics_state.insert_sensor(
+        physics::TriggerShape::Ball { radius: player::sensor_radius(MIN_DIST) },
+        physics::vector![position.x, position.y, position.z],
+    );
+
+    let texture = assets.register(&[&config.model, "texture"].join("::"));
+    let mesh = assets.register(&[&config.model, "mesh"].join("::"));
 
     world.spawn(Some((
         Model::from(mesh),
         Material {
             texture,
+            albedo: config.color.unwrap_or(Color::white()),
             ..Default::default()
         },
         Transform {
             scale: Vec3::new(SCALE, SCALE, SCALE),
             ..Default::default()
         },
-        State{ position },
+        State {
+            position,
+            config,
+            collected: false,
+            respawn_left: None,
+            added: 0,
+            budget_change: 0,
+        },
+        time::History::<State>::default(),
+        physics::Sensor { collider: sensor },
         Render::default(),
     )));
 }
 
 pub fn control(
-    mut world: Mut<World>,
-    mut time_stack: Mut<time::Stack>,
+    world: &World,
+    physics_state: &physics::State,
+    time_stack: &mut time::Stack,
 ) {
-    // player
-    let mut player_x = 0.0;
-    let mut player_y = 0.0;
-    let mut player_z = 0.0;
-    let query = world.query::<(&player::State, &Transform)>();
-    for (_, transform) in query {
-        player_x = transform.translate.x;
-        player_y = transform.translate.y;
-        player_z = transform.translate.z;
-    }
+    let player_collider = match player::collider(world) {
+        Some(collider) => collider,
+        None => return,
+    };
 
     // time capsule
-    let mut to_exile = Vec::new();
+    let query = world.query::<(&mut State, &mut Transform, &physics::Sensor)>();
+    let dt = physics_state.dt();
 
-    let query = world.query::<(&Entity, &mut State, &mut Transform)>();
+    for (state, transform, sensor) in query {
+        // rewinding past a pickup takes its time back, scrubbing forward over
+        // it gives the time again
+        if state.budget_change > 0 {
+            time_stack.add_budget(state.budget_change as usize);
+        } else {
+            time_stack.spend_budget(-state.budget_change as usize);
+        }
+        state.budget_change = 0;
+
+        if let Some(respawn_left) = state.respawn_left {
+            if respawn_left <= dt {
+                state.collected = false;
+                state.respawn_left = None;
+            } else {
+                state.respawn_left = Some(respawn_left - dt);
+            }
+        }
+
+        // collected capsules are kept hidden so that a rewind can bring them back
+        if state.collected {
+            transform.scale = Vec3::new(0.0, 0.0, 0.0);
+            continue;
+        }
+
+        transform.scale = Vec3::new(SCALE, SCALE, SCALE);
 
-    for (entity, state, transform) in query {
         // simple animation
         let theta: f32 = 0.02;
         let q = Quat::from_sv(
@@ -79,19 +186,20 @@ pub fn control(
 
         transform.translate = state.position;
 
-        let dist_to_capsule = (
-            (player_x-state.position.x).powf(2.0)+
-            (player_y-state.position.y).powf(2.0)+
-            (player_z-state.position.z).powf(2.0)
-        ).sqrt();
+        let capacity = time_stack.capacity();
+        let amount = state.config.amount.unwrap_or(capacity / 2);
 
-        if (dist_to_capsule<=MIN_DIST) && (time_stack.index_max<time::STACK_MAX_SIZE) {
-            time_stack.index_max += time::STACK_MAX_SIZE / 2;
-            to_exile.push(*entity);
-        }
-    }
+        let collectable = match state.config.overflow {
+            Overflow::Refuse => time_stack.budget() + amount <= capacity,
+            Overflow::Clamp => time_stack.budget() < capacity,
+        };
 
-    for i in 0..to_exile.len() {
-        world.exile(to_exile[i]);
+        if physics_state.is_intersecting(player_collider, sensor.collider) && collectable {
+            let budget = time_stack.budget();
+            time_stack.add_budget(amount);
+            state.added += time_stack.budget() - budget;
+            state.collected = true;
+            state.respawn_left = state.config.respawn_delay;
+        }
     }
 }
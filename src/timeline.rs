@@ -0,0 +1,451 @@
+/// Fixed-capacity ring buffer keeping a value per tick, the newest tick has
+/// the age 0 and the oldest one is dropped when the buffer is full
+#[derive(Clone, Debug)]
+pub struct Timeline<T> {
+    slots: Vec<Option<T>>,
+    /// Slot of the newest tick
+    head: usize,
+    /// Number of ticks kept, the valid ages are `0..len`
+    len: usize,
+}
+
+impl<T> Default for Timeline<T> {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl<T> Timeline<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            slots: (0..capacity).map(|_| None).collect(),
+            head: 0,
+            len: 0,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Changes the capacity keeping the newest ticks
+    pub fn set_capacity(&mut self, capacity: usize) {
+        if capacity == self.capacity() {
+            return;
+        }
+
+        let mut timeline = Self::new(capacity);
+        for age in (0..self.len.min(capacity)).rev() {
+            timeline.push(self.take(age));
+        }
+
+        *self = timeline;
+    }
+
+    /// Adds the newest tick, dropping the oldest one if the buffer is full
+    pub fn push(&mut self, item: Option<T>) {
+        let capacity = self.capacity();
+        if capacity == 0 {
+            return;
+        }
+
+        self.head = (self.head + 1) % capacity;
+        self.slots[self.head] = item;
+        self.len = (self.len + 1).min(capacity);
+    }
+
+    /// Records the tick at the cursor: the newest tick is added when the
+    /// cursor is at the present, otherwise the replayed tick is overwritten
+    pub fn record(&mut self, item: Option<T>, cursor: &Cursor) {
+        match cursor.age() {
+            Some(age) => self.set(age, item),
+            None => self.push(item),
+        }
+    }
+
+    /// Returns the tick at the cursor
+    pub fn at(&self, cursor: &Cursor) -> Option<&T> {
+        cursor.age().and_then(|age| self.get(age))
+    }
+
+    /// Iterates the ticks before the cursor, from the newest to the oldest
+    pub fn before(&self, cursor: &Cursor) -> impl Iterator<Item = Option<&T>> + '_ {
+        (cursor.index..self.len).map(move |age| self.get(age))
+    }
+
+    fn slot(&self, age: usize) -> Option<usize> {
+        if age < self.len {
+            Some((self.head + self.capacity() - age) % self.capacity())
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, age: usize) -> Option<&T> {
+        self.slot(age).and_then(|slot| self.slots[slot].as_ref())
+    }
+
+    pub fn set(&mut self, age: usize, item: Option<T>) {
+        if let Some(slot) = self.slot(age) {
+            self.slots[slot] = item;
+        }
+    }
+
+    pub fn take(&mut self, age: usize) -> Option<T> {
+        match self.slot(age) {
+            Some(slot) => self.slots[slot].take(),
+            None => None,
+        }
+    }
+
+    /// Iterates from the newest to the oldest tick
+    pub fn iter(&self) -> impl Iterator<Item = Option<&T>> + '_ {
+        (0..self.len).map(move |age| self.get(age))
+    }
+}
+
+/// Position of the time in the ticks recorded by the timelines of a level,
+/// together with the rewind budget limiting how far it can be moved back
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    /// Ticks kept by the timelines
+    capacity: usize,
+    /// Ticks run since the level start
+    count: usize,
+    /// Age of the tick the time was rewound to plus one, 0 at the present
+    index: usize,
+    /// Ticks above this age were replayed or cleared
+    index_cleared: usize,
+    /// Rewind budget in ticks
+    budget: usize,
+    /// Ticks rewound by the current rewind, paid when it is finished
+    rewinding: usize,
+}
+
+impl Cursor {
+    /// Creates a cursor at the level start for timelines of the given
+    /// capacity and the starting rewind budget in ticks
+    pub fn new(capacity: usize, budget: usize) -> Self {
+        Self {
+            capacity,
+            budget: budget.min(capacity),
+            ..Default::default()
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Age of the recorded tick the time is at, None at the present
+    pub fn age(&self) -> Option<usize> {
+        self.index.checked_sub(1)
+    }
+
+    /// Ticks run up to the cursor since the level start
+    pub fn tick(&self) -> usize {
+        self.count - self.index
+    }
+
+    /// Ticks kept by the timelines
+    pub fn recorded(&self) -> usize {
+        self.count.min(self.capacity)
+    }
+
+    /// Rewind ticks left
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Adds rewind ticks, up to the capacity
+    pub fn add_budget(&mut self, ticks: usize) {
+        self.budget = (self.budget + ticks).min(self.capacity);
+    }
+
+    pub fn spend_budget(&mut self, ticks: usize) {
+        self.budget = self.budget.saturating_sub(ticks);
+    }
+
+    /// Ticks rewound by the current rewind
+    pub fn rewinding(&self) -> usize {
+        self.rewinding
+    }
+
+    /// Ticks since the last cleared tick
+    pub fn elapsed(&self) -> usize {
+        self.count - self.index_cleared
+    }
+
+    /// Ticks the time is rewound by and going to be replayed
+    pub fn rewound(&self) -> usize {
+        self.index - self.index_cleared
+    }
+
+    /// Whether the tick at the cursor is a rewound tick being replayed
+    pub fn is_replaying(&self) -> bool {
+        self.index > self.index_cleared
+    }
+
+    /// Ticks that can be rewound from the last cleared tick
+    pub fn available(&self) -> usize {
+        self.budget + self.index - self.rewinding - self.index_cleared
+    }
+
+    /// Whether the cursor cannot be moved further to the past
+    pub fn at_rewind_limit(&self) -> bool {
+        self.index >= self.recorded() || self.rewinding >= self.budget
+    }
+
+    /// Moves the cursor one tick to the past, returns false at the limit
+    pub fn step_back(&mut self) -> bool {
+        if self.at_rewind_limit() {
+            return false;
+        }
+
+        self.index += 1;
+        self.rewinding += 1;
+        true
+    }
+
+    /// Moves the cursor one tick to the future, or runs a new tick
+    pub fn step_forward(&mut self) {
+        if self.index > 0 {
+            self.index -= 1;
+        } else {
+            self.count += 1;
+        }
+
+        self.index_cleared = self.index_cleared.min(self.index);
+    }
+
+    /// Moves the cursor one tick back to the future within the current
+    /// rewind, keeping at least one rewound tick; returns false if it cannot
+    pub fn scrub_forward(&mut self) -> bool {
+        if self.rewinding <= 1 {
+            return false;
+        }
+
+        self.index -= 1;
+        self.rewinding -= 1;
+        true
+    }
+
+    /// Pays for the ticks rewound by the current rewind, returns them
+    pub fn finish_rewind(&mut self) -> usize {
+        let ticks = self.rewinding;
+        self.spend_budget(ticks);
+        self.rewinding = 0;
+        ticks
+    }
+
+    /// Returns the ages of the rewound ticks not yet replayed, which are no
+    /// longer going to be replayed
+    pub fn clear_rewound(&mut self) -> std::ops::Range<usize> {
+        let range = self.index_cleared..self.index;
+        self.index_cleared = self.index;
+        range
+    }
+
+    /// Returns the cursor moved to an earlier recorded tick, the ticks up to
+    /// the current position are going to be replayed and the later ones are
+    /// cleared; None if the tick is not recorded
+    pub fn at_tick(&self, tick: usize) -> Option<Self> {
+        let index = self.count.checked_sub(tick)?;
+
+        if index == 0 || index > self.recorded() {
+            return None;
+        }
+
+        Some(Self {
+            index,
+            index_cleared: self.index.min(index),
+            rewinding: 0,
+            ..*self
+        })
+    }
+
+    /// Moves to the position of another cursor keeping the budget
+    pub fn move_to(&mut self, other: &Self) {
+        self.count = other.count;
+        self.index = other.index;
+        self.index_cleared = other.index_cleared;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timeline(capacity: usize, ticks: std::ops::Range<u32>) -> Timeline<u32> {
+        let mut timeline = Timeline::new(capacity);
+        for tick in ticks {
+            timeline.push(Some(tick));
+        }
+        timeline
+    }
+
+    fn cursor(capacity: usize, budget: usize, ticks: usize) -> Cursor {
+        let mut cursor = Cursor::new(capacity, budget);
+        for _ in 0..ticks {
+            cursor.step_forward();
+        }
+        cursor
+    }
+
+    #[test]
+    fn push_past_capacity_drops_the_oldest_tick() {
+        let timeline = timeline(3, 0..5);
+
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline.iter().collect::<Vec<_>>(), vec![Some(&4), Some(&3), Some(&2)]);
+    }
+
+    #[test]
+    fn push_to_zero_capacity_keeps_nothing() {
+        let timeline = timeline(0, 0..5);
+
+        assert!(timeline.is_empty());
+        assert_eq!(timeline.get(0), None);
+    }
+
+    #[test]
+    fn record_at_the_present_pushes() {
+        let mut timeline = timeline(4, 0..2);
+        timeline.record(Some(9), &Cursor::new(4, 0));
+
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline.get(0), Some(&9));
+        assert_eq!(timeline.get(1), Some(&1));
+    }
+
+    #[test]
+    fn record_at_a_rewound_tick_overwrites_it() {
+        let mut timeline = timeline(4, 0..3);
+        let mut cursor = cursor(4, 4, 3);
+        cursor.step_back();
+        cursor.step_back();
+        timeline.record(Some(9), &cursor);
+
+        assert_eq!(timeline.len(), 3);
+        assert_eq!(timeline.iter().collect::<Vec<_>>(), vec![Some(&2), Some(&9), Some(&0)]);
+        assert_eq!(timeline.at(&cursor), Some(&9));
+    }
+
+    #[test]
+    fn get_and_take_out_of_range() {
+        let mut timeline = timeline(4, 0..2);
+
+        assert_eq!(timeline.get(2), None);
+        assert_eq!(timeline.take(2), None);
+        assert_eq!(timeline.take(1), Some(0));
+        assert_eq!(timeline.get(1), None);
+        assert_eq!(timeline.len(), 2);
+    }
+
+    #[test]
+    fn set_capacity_keeps_the_newest_ticks() {
+        let mut timeline = timeline(4, 0..4);
+
+        timeline.set_capacity(2);
+        assert_eq!(timeline.iter().collect::<Vec<_>>(), vec![Some(&3), Some(&2)]);
+
+        timeline.set_capacity(3);
+        timeline.push(Some(4));
+        assert_eq!(timeline.capacity(), 3);
+        assert_eq!(timeline.iter().collect::<Vec<_>>(), vec![Some(&4), Some(&3), Some(&2)]);
+    }
+
+    #[test]
+    fn before_skips_the_ticks_after_the_cursor() {
+        let timeline = timeline(4, 0..4);
+        let mut cursor = cursor(4, 4, 4);
+        cursor.step_back();
+
+        assert_eq!(timeline.before(&cursor).collect::<Vec<_>>(), vec![Some(&2), Some(&1), Some(&0)]);
+    }
+
+    #[test]
+    fn cursor_steps_back_within_the_budget() {
+        let mut cursor = cursor(10, 2, 5);
+
+        assert_eq!(cursor.age(), None);
+        assert!(cursor.step_back());
+        assert!(cursor.step_back());
+        assert!(!cursor.step_back());
+        assert_eq!(cursor.age(), Some(1));
+        assert_eq!(cursor.tick(), 3);
+
+        assert_eq!(cursor.finish_rewind(), 2);
+        assert_eq!(cursor.budget(), 0);
+        assert!(!cursor.step_back());
+    }
+
+    #[test]
+    fn cursor_steps_back_within_the_recorded_ticks() {
+        let mut cursor = cursor(3, 3, 2);
+
+        assert!(cursor.step_back());
+        assert!(cursor.step_back());
+        assert!(!cursor.step_back());
+        assert!(cursor.at_rewind_limit());
+        assert_eq!(cursor.age(), Some(1));
+    }
+
+    #[test]
+    fn cursor_scrubs_forward_within_the_rewind() {
+        let mut cursor = cursor(10, 10, 5);
+        cursor.step_back();
+        cursor.step_back();
+
+        assert!(cursor.scrub_forward());
+        assert!(!cursor.scrub_forward());
+        assert_eq!(cursor.age(), Some(0));
+        assert_eq!(cursor.finish_rewind(), 1);
+    }
+
+    #[test]
+    fn cursor_replays_and_clears_the_rewound_ticks() {
+        let mut cursor = cursor(10, 10, 5);
+        cursor.step_back();
+        cursor.step_back();
+        cursor.step_back();
+        cursor.finish_rewind();
+
+        cursor.step_forward();
+        assert!(cursor.is_replaying());
+        assert_eq!(cursor.rewound(), 2);
+        assert_eq!(cursor.elapsed(), 5);
+
+        assert_eq!(cursor.clear_rewound(), 0..2);
+        assert!(!cursor.is_replaying());
+        assert_eq!(cursor.rewound(), 0);
+        assert_eq!(cursor.elapsed(), 3);
+
+        cursor.step_forward();
+        cursor.step_forward();
+        cursor.step_forward();
+        assert_eq!(cursor.age(), None);
+        assert_eq!(cursor.elapsed(), 6);
+    }
+
+    #[test]
+    fn cursor_at_tick_is_limited_to_the_recorded_ticks() {
+        let cursor = cursor(4, 4, 6);
+
+        assert_eq!(cursor.at_tick(6), None);
+        assert_eq!(cursor.at_tick(1), None);
+
+        let split = cursor.at_tick(3).unwrap();
+        assert_eq!(split.age(), Some(2));
+        assert_eq!(split.rewound(), 3);
+        assert_eq!(split.budget(), cursor.budget());
+    }
+}
@@ -2,27 +2,80 @@ use std::f32::consts::PI;
 use std::sync::{ Arc, Mutex, };
 
 use dotrix::{
-    Assets, World, Transform, Input, Id,
+    Assets, World, Transform, Id,
     assets::Mesh,
     pbr::{ Model, Material, },
     math::{ Vec3, },
-    ecs::{ Mut, Const, },
     math::{ Quat },
     renderer::Render,
 };
 
-use crate::physics::{ self, vector, nalgebra, };
+use crate::physics::{ self, vector, };
 use crate::player;
-use crate::actions::Action;
+use crate::actions::{ Action, TickInput, };
+use crate::signal;
 use crate::time;
 
-const TRAMP_MIN_DIST: f32 = 1.75;
-const BUTTON_MIN_DIST: f32 = 1.5;
+pub const TRAMP_MIN_DIST: f32 = 1.75;
+pub const BUTTON_MIN_DIST: f32 = 1.5;
+pub const IMPULSE: (f32, f32, f32) = (0.0, 90.0, 0.0);
+
+/// Tunable trampoline behaviour
+pub struct Config {
+    pub impulse: Vec3,
+    pub radius: f32,
+    pub button_radius: f32,
+    /// Launches available after each activation
+    pub charges: u32,
+    /// Seconds after the last charge is used until the trampoline re-arms itself
+    pub cooldown: Option<f32>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            impulse: Vec3::new(IMPULSE.0, IMPULSE.1, IMPULSE.2),
+            radius: TRAMP_MIN_DIST,
+            button_radius: BUTTON_MIN_DIST,
+            charges: 1,
+            cooldown: None,
+        }
+    }
+}
 
 pub struct State {
     base_position: Vec3,
-    button_position: Vec3,
+    config: Config,
     active: bool,
+    charges_left: u32,
+    cooldown_left: Option<f32>,
+}
+
+impl State {
+    fn arm(&mut self) {
+        self.active = true;
+        self.charges_left = self.config.charges.max(1);
+        self.cooldown_left = None;
+    }
+}
+
+/// Marks an activation button entity, several buttons can share one trampoline state
+pub struct Button {}
+
+impl time::Rewindable for Arc<Mutex<State>> {
+    type Snapshot = (bool, u32, Option<f32>);
+
+    fn snapshot(&self) -> (bool, u32, Option<f32>) {
+        let state = self.lock().unwrap();
+        (state.active, state.charges_left, state.cooldown_left)
+    }
+
+    fn restore(&mut self, snapshot: &(bool, u32, Option<f32>)) {
+        let mut state = self.lock().unwrap();
+        state.active = snapshot.0;
+        state.charges_left = snapshot.1;
+        state.cooldown_left = snapshot.2;
+    }
 }
 
 pub fn load_assets(
@@ -38,16 +91,33 @@ pub fn spawn(
     assets: &mut Assets,
     physics_state: &mut physics::State,
     base_position: Vec3,
-    button_position: Vec3,
+    button_positions: Vec<Vec3>,
+    config: Config,
+    receiver: signal::Receiver,
 ) {
     let texture = assets.register("trampoline::texture");
     let mesh_id = assets.register("trampoline::mesh");
 
-    let state = Arc::new(Mutex::new(State {
+    let mut state = State {
         base_position,
-        button_position,
+        config,
         active: false,
-    }));
+        charges_left: 0,
+        cooldown_left: None,
+    };
+
+    // without buttons or signals the trampoline is always armed
+    if button_positions.is_empty() && !receiver.has_id() {
+        state.arm();
+    }
+
+    let trampoline_sensor = physics_state.insert_sensor(
+        physics::TriggerShape::Ball { radius: player::sensor_radius(state.config.radius) },
+        vector![base_position.x, base_position.y, base_position.z],
+    );
+    let button_radius = player::sensor_radius(state.config.button_radius);
+
+    let state = Arc::new(Mutex::new(state));
 
     // spawn trampoline
     world.spawn(Some((
@@ -63,97 +133,85 @@ pub fn spawn(
         },
         Render::default(),
         state.clone(),
-        time::ActionableObject {
-            active: false,
-            selected: false,
-            is_player: false,
-            tile_texture_name: "trampoline",
-        },
+        time::ActionableObject::new("trampoline", false),
+        physics::Sensor { collider: trampoline_sensor },
+        receiver,
+        time::History::<time::ActionableObject>::default(),
+        time::History::<Arc<Mutex<State>>>::default(),
+        time::History::<signal::Receiver>::default(),
     )));
 
     // add trampoline the collider set
     let mesh = assets.get(mesh_id).unwrap();
 
-    let mut indices = Vec::new();
-
-    let vertices = mesh.vertices_as::<[f32; 3]>(0).collect::<Vec<_>>()
-        .iter().map(|elem| physics::nalgebra::Point3::new(
-                elem[0],
-                elem[1],
-                elem[2],
-            )
-        ).collect();
-
-    let indices_mesh = mesh.indices().take()
-        .expect("trampoline mesh should contain indices");
-
-    for i in 0..indices_mesh.len()/3 {
-        indices.push([
-            indices_mesh[i*3],
-            indices_mesh[i*3+1],
-            indices_mesh[i*3+2],
-        ]);
-    }
-
-    let collider = physics::ColliderBuilder::trimesh(
-        vertices,
-        indices,
-    ).translation(vector![base_position.x, 0.0, base_position.z]).build();
+    let collider = physics::trimesh_collider(mesh)
+        .translation(vector![base_position.x, 0.0, base_position.z])
+        .build();
 
     physics_state.physics.as_mut().expect("physics::State must be defined")
         .collider_set.insert(collider);
 
-    // add activation button
-    world.spawn(Some((
-        Model::from(mesh_id),
-        Material {
-            texture,
-            ..Default::default()
-        },
-        Transform {
-            translate: button_position,
-            rotate: Quat::new((PI/4.0).cos(), 0.0, 0.0, (PI/4.0).sin()),
-            scale: Vec3::new(0.4, 1.0, 0.4)
-        },
-        Render::default(),
-        state.clone(),
-    )));
+    // add activation buttons
+    for button_position in button_positions {
+        let button_sensor = physics_state.insert_sensor(
+            physics::TriggerShape::Ball { radius: button_radius },
+            vector![button_position.x, button_position.y, button_position.z],
+        );
+
+        world.spawn(Some((
+            Model::from(mesh_id),
+            Material {
+                texture,
+                ..Default::default()
+            },
+            Transform {
+                translate: button_position,
+                rotate: Quat::new((PI/4.0).cos(), 0.0, 0.0, (PI/4.0).sin()),
+                scale: Vec3::new(0.4, 1.0, 0.4)
+            },
+            Render::default(),
+            state.clone(),
+            Button {},
+            physics::Sensor { collider: button_sensor },
+        )));
+    }
 }
 
 pub fn control(
-    world: Const<World>,
-    input: Const<Input>,
-    mut physics_state: Mut<physics::State>,
+    world: &World,
+    input: &TickInput,
+    physics_state: &mut physics::State,
+    time_stack: &mut time::Stack,
 ) {
-
-
     // query player
+    let player_collider = match player::collider(world) {
+        Some(collider) => collider,
+        None => return,
+    };
+
     let mut query = world.query::<(&physics::RigidBodyHandle, &mut player::State,)>();
 
     let (rigid_body, _) = query.next().take().expect("player is not found");
 
-    let rigid_body_set = &mut physics_state.physics
-            .as_mut().expect("physics::State must be defined")
-            .rigid_body_set;
+    let is_player_in = |sensor: &physics::Sensor| {
+        physics_state.is_intersecting(player_collider, sensor.collider)
+    };
 
-    let body = rigid_body_set.get_mut(*rigid_body).unwrap();
-    let player_position = body.position().translation;
+    let mut impulses = Vec::new();
+    let mut new_action = false;
 
     // query button
-    let query = world.query::<(&mut Transform, &mut Arc<Mutex<State>>,)>();
+    let query = world.query::<(
+        &mut Transform,
+        &mut Arc<Mutex<State>>,
+        &Button,
+        &physics::Sensor,
+    )>();
 
-    for (transform, state) in query {
+    for (transform, state, _, sensor) in query {
         let mut state = state.lock().unwrap();
-        if !state.active {
-            let distance_to_button = (
-                (player_position.x - state.button_position.x).powf(2.0) +
-                (player_position.y - state.button_position.y).powf(2.0) +
-                (player_position.z - state.button_position.z).powf(2.0)
-            ).sqrt();
-
-            if distance_to_button <= BUTTON_MIN_DIST {
-                state.active = true;
-            }
+        if !state.active && physics_state.intersection_started(player_collider, sensor.collider) {
+            state.arm();
         }
 
         if state.active {
@@ -169,24 +227,46 @@ pub fn control(
         &mut Transform,
         &mut Arc<Mutex<State>>,
         &mut time::ActionableObject,
+        &physics::Sensor,
+        &signal::Receiver,
     )>();
 
-    for (transform, state, object) in query {
+    let dt = physics_state.dt();
+
+    for (transform, state, object, sensor, receiver) in query {
         let mut state = state.lock().unwrap();
-        if state.active {
-            let distance_to_tramp = (
-                (player_position.x - state.base_position.x).powf(2.0) +
-                (player_position.y - state.base_position.y).powf(2.0) +
-                (player_position.z - state.base_position.z).powf(2.0)
-            ).sqrt();
-
-            if (distance_to_tramp <= TRAMP_MIN_DIST) &&
-                input.is_action_activated(Action::TurnRight) &&
-                object.selected
-            {
-                println!("dist to tramp: {:?}", distance_to_tramp);
-                body.apply_impulse(vector![0.0, 90.0, 0.0], true);
+
+        if !state.active && receiver.is_activated() {
+            state.arm();
+        }
+
+        if let Some(cooldown_left) = state.cooldown_left {
+            if cooldown_left <= dt {
+                state.arm();
+            } else {
+                state.cooldown_left = Some(cooldown_left - dt);
+            }
+        }
+
+        // a replayed use fires the trampoline again at the same tick
+        let mut used = matches!(object.current_action, Some(time::ObjectAction::Use));
+
+        if state.active && is_player_in(sensor) &&
+            input.is_action_activated(Action::TurnRight) &&
+            object.selected
+        {
+            object.current_action = Some(time::ObjectAction::Use);
+            used = true;
+            new_action = true;
+        }
+
+        if state.active && is_player_in(sensor) && used {
+            impulses.push(state.config.impulse);
+
+            state.charges_left = state.charges_left.saturating_sub(1);
+            if state.charges_left == 0 {
                 state.active = false;
+                state.cooldown_left = state.config.cooldown;
             }
         }
 
@@ -198,4 +278,19 @@ pub fn control(
 
         object.active = state.active;
     }
+
+    let rigid_body_set = &mut physics_state.physics
+        .as_mut().expect("physics::State must be defined")
+        .rigid_body_set;
+
+    let body = rigid_body_set.get_mut(*rigid_body).unwrap();
+
+    for impulse in impulses {
+        body.apply_impulse(vector![impulse.x, impulse.y, impulse.z], true);
+    }
+
+    // an action given while the time is replayed changes the past
+    if new_action {
+        time::change_past(world, time_stack, physics_state);
+    }
 }
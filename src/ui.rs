@@ -2,7 +2,7 @@ use dotrix::ecs::{ Mut, Const, Context, };
 use dotrix::{ Window, Input, State as StateStack, Frame, Assets, World, };
 use dotrix::overlay::Overlay;
 use dotrix::window::Fullscreen;
-use dotrix::math::{ Vec2u, Vec3, };
+use dotrix::math::{ Vec2u, };
 use dotrix::egui::{
     self,
     Egui,
@@ -10,14 +10,20 @@ use dotrix::egui::{
 
 use crate::states;
 use crate::actions;
+use crate::checkpoint;
 use crate::time;
 use crate::level;
+use crate::records;
+use crate::replay;
 use crate::ui_clock::Clock;
+use std::collections::HashMap;
 use std::f32::consts::PI;
 use std::fs;
 
 pub struct Ctx {
     level_list: Vec<String>,
+    level_errors: HashMap<String, level::LevelError>,
+    error: Option<level::LevelError>,
     frame: egui::containers::Frame,
     offset: f32,
 }
@@ -33,13 +39,37 @@ impl Default for Ctx {
         let level_folder = "levels/";
 
         let mut level_list = Vec::new();
+        let mut level_errors = HashMap::new();
 
-        for file in fs::read_dir(["./", level_folder].join("")).unwrap() {
-            level_list.push(file.unwrap().path().file_stem().unwrap().to_str().unwrap().to_string());
+        match fs::read_dir(["./", level_folder].join("")) {
+            Ok(files) => {
+                for path in files.filter_map(|file| file.ok()).map(|file| file.path()) {
+                    if path.extension().and_then(|ext| ext.to_str()) != Some("yaml") {
+                        continue;
+                    }
+
+                    let name = match path.file_stem().and_then(|stem| stem.to_str()) {
+                        Some(name) => name.to_string(),
+                        None => continue,
+                    };
+
+                    if let Err(e) = level::Level::from_file(&[&name, ".yaml"].join("")) {
+                        println!("Broken level {}", e);
+                        level_errors.insert(name.clone(), e);
+                    }
+
+                    level_list.push(name);
+                }
+            },
+            Err(e) => println!("Cannot read the levels folder! {}", e),
         }
 
+        level_list.sort();
+
         Self {
             level_list,
+            level_errors,
+            error: None,
             frame,
             offset: 10.0,
         }
@@ -54,6 +84,7 @@ pub enum WindowMode {
 
 pub struct State {
     pub show_info_panel: bool,
+    pub show_ghost: bool,
     window_mode: WindowMode,
 }
 
@@ -61,6 +92,7 @@ impl Default for State {
     fn default() -> Self {
         Self {
             show_info_panel: false,
+            show_ghost: true,
             window_mode: WindowMode::Windowed,
         }
     }
@@ -82,20 +114,43 @@ pub fn init(
 }
 
 pub fn draw_main_menu(
-    ctx: Context<Ctx>,
+    mut ctx: Context<Ctx>,
     overlay: Const<Overlay>,
     mut state_stack: Mut<StateStack>,
     mut stats_opt: Mut<Option<states::Stats>>,
     mut level_opt: Mut<Option<level::Level>>,
     mut window: Mut<Window>,
+    records: Const<records::Records>,
+    mut replay_state: Mut<replay::State>,
 ) {
     let state = state_stack.get_mut::<states::MainMenu>()
         .expect("something terrible has happened");
 
-    if !state.selected_level.is_some() {
-        let egui = overlay.get::<Egui>()
-            .expect("Renderer does not contain an Overlay instance");
+    let egui = overlay.get::<Egui>()
+        .expect("Renderer does not contain an Overlay instance");
+
+    if let Some(error) = ctx.error.clone() {
+        egui::containers::Window::new("Broken level")
+            .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::new(0.0, 0.0))
+            .collapsible(false)
+            .resizable(false)
+            .default_width(320.0)
+            .show(&egui.ctx, |ui| {
+                ui.vertical_centered_justified(|ui| {
+                    ui.label(error.to_string());
+
+                    ui.add_space(25.0);
+
+                    if ui.button("OK").clicked() {
+                        ctx.error = None;
+                    }
+                });
+            });
+
+        return;
+    }
 
+    if !state.selected_level.is_some() {
         egui::containers::Window::new("Main menu")
             .anchor(egui::Align2::CENTER_CENTER, egui::Vec2::new(0.0, 0.0))
             .frame(ctx.frame)
@@ -104,11 +159,48 @@ pub fn draw_main_menu(
             .default_width(160.0)
             .show(&egui.ctx, |ui| {
                 ui.vertical_centered_justified(|ui| {
-                    for level in &ctx.level_list {
-                        if ui.button(level).clicked() {
-                            state.selected_level = Some(level.to_string());
+                    egui::Grid::new("level_grid").show(ui, |ui| {
+                        for level in &ctx.level_list {
+                            if ui.button(level).clicked() {
+                                replay_state.play(None);
+                                state.selected_level = Some(level.to_string());
+                            }
+
+                            let record_text = match records.get(level) {
+                                _ if ctx.level_errors.contains_key(level) =>
+                                    "broken".to_string(),
+                                Some(records::LevelRecord {
+                                    best_time: Some(best_time),
+                                    last_time,
+                                    completions,
+                                }) => format!(
+                                    "best {:04.1}  last {:04.1}  passed {}x",
+                                    best_time,
+                                    last_time.unwrap_or(*best_time),
+                                    completions,
+                                ),
+                                _ => "not passed yet".to_string(),
+                            };
+
+                            let record_color = if ctx.level_errors.contains_key(level) {
+                                egui::Color32::RED
+                            } else {
+                                egui::Color32::GRAY
+                            };
+
+                            ui.add(egui::Label::new(
+                                egui::RichText::new(record_text).color(record_color)
+                            ));
+
+                            if replay::Replay::exists(level) {
+                                if ui.button("Replay").clicked() {
+                                    replay_state.play(replay::Replay::load(level));
+                                    state.selected_level = Some(level.to_string());
+                                }
+                            }
+                            ui.end_row();
                         }
-                    }
+                    });
 
                     ui.add_space(25.0);
 
@@ -119,13 +211,21 @@ pub fn draw_main_menu(
             });
     }
 
-    if let Some(level) = &state.selected_level {
-        let level = level::Level::from_file(&[level, ".yaml"].join(""));
-        *stats_opt = Some(states::Stats::new(
-            Vec3::new(level.target_position.0, level.target_position.1, level.target_position.2)
-        ));
-        *level_opt = Some(level);
-        state_stack.push(states::LoadAssets::default());
+    if let Some(level_name) = state.selected_level.clone() {
+        match level::Level::from_file(&[&level_name, ".yaml"].join("")) {
+            Ok(level) => {
+                ctx.level_errors.remove(&level_name);
+                *stats_opt = Some(states::Stats::new(&level));
+                *level_opt = Some(level);
+                state_stack.push(states::LoadAssets::default());
+            },
+            Err(e) => {
+                println!("Broken level {}", e);
+                ctx.level_errors.insert(level_name, e.clone());
+                ctx.error = Some(e);
+                state.selected_level = None;
+            },
+        }
     }
 }
 
@@ -157,6 +257,7 @@ pub fn draw_menu(
     mut window: Mut<Window>,
     frame: Const<Frame>,
     stats_opt: Const<Option<states::Stats>>,
+    replay_state: Const<replay::State>,
 ) {
     let stats = stats_opt.as_ref()
         .expect("Game stats should be initialized");
@@ -195,7 +296,9 @@ pub fn draw_menu(
         .expect("Renderer does not contain an Overlay instance");
 
     // pause menu
-    let label = if stats.level_passed {
+    let label = if stats.level_passed && stats.new_record {
+        format!("New record! Level passed in {:04.1} secs", stats.time)
+    } else if stats.level_passed {
         format!("Level passed in {:04.1} secs", stats.time)
     } else {
         "Pause".to_string()
@@ -213,6 +316,13 @@ pub fn draw_menu(
                         if ui.button("Resume").clicked() {
                             state_stack.pop_any();
                         }
+                    } else if let Some(desynced) = replay_state.desynced {
+                        let text = if desynced { "Replay desynced" } else { "Replay verified" };
+                        ui.label(text);
+                    } else if !replay_state.is_playing() {
+                        if ui.button("Save replay").clicked() {
+                            replay_state.save(&stats.level_name);
+                        }
                     }
 
                     if settings.show_info_panel == true {
@@ -225,6 +335,16 @@ pub fn draw_menu(
                         }
                     }
 
+                    if settings.show_ghost == true {
+                        if ui.button("Hide ghost").clicked() {
+                            settings.show_ghost = false;
+                        }
+                    } else {
+                        if ui.button("Show ghost").clicked() {
+                            settings.show_ghost = true;
+                        }
+                    }
+
                     if settings.window_mode == WindowMode::BorderlessFullscreen {
                         if ui.button("Windowed").clicked() {
                             window.set_fullscreen(None);
@@ -308,6 +428,7 @@ pub fn draw_in_game_panels(
     overlay: Const<Overlay>,
     stats_opt: Const<Option<states::Stats>>,
     time_stack: Const<time::Stack>,
+    state_stack: Const<StateStack>,
     mut assets: Mut<Assets>,
 ) {
     let stats = stats_opt.as_ref()
@@ -323,7 +444,23 @@ pub fn draw_in_game_panels(
         ..Default::default()
     };
 
-    let scale = time::STACK_MAX_SIZE as f32;
+    let scale = time_stack.capacity().max(1) as f32;
+
+    // merge the consecutive slow motion ticks into clock segments
+    let mut slowed: Vec<(f32, f32)> = Vec::new();
+    for age in time_stack.slowed_ticks() {
+        let tick = time_stack.elapsed() as f32 - age as f32;
+        let angle = (tick - 1.0) / scale*2.0*PI;
+        let length = 1.0 / scale*2.0*PI;
+
+        match slowed.last_mut() {
+            Some(last) if (last.0 - (angle + length)).abs() < length / 2.0 => {
+                last.0 = angle;
+                last.1 += length;
+            },
+            _ => slowed.push((angle, length)),
+        }
+    }
 
     egui::containers::Window::new("clock")
         .anchor(egui::Align2::RIGHT_TOP, egui::Vec2::new(-ctx.offset, ctx.offset))
@@ -333,12 +470,27 @@ pub fn draw_in_game_panels(
         .show(&egui.ctx, |ui| {
             ui.add(
                 Clock::new(
-                    (time_stack.count-time_stack.index_cleared) as f32 / scale*2.0*PI,
-                    (time_stack.index-time_stack.index_cleared) as f32 / scale*2.0*PI,
-                    (time_stack.index_max+time_stack.index-time_stack.di-time_stack.index_cleared) as f32 /
-                        scale*2.0*PI,
-                )
+                    time_stack.elapsed() as f32 / scale*2.0*PI,
+                    time_stack.rewound() as f32 / scale*2.0*PI,
+                    time_stack.available() as f32 / scale*2.0*PI,
+                ).slowed(slowed)
             );
+
+            if let Some(rewind_state) = state_stack.get::<states::RewindTime>() {
+                let label = if rewind_state.scrubbing {
+                    String::from("scrubbing")
+                } else {
+                    format!("rewind x{}", rewind_state.speed)
+                };
+
+                ui.add(egui::Label::new(egui::RichText::new(label)
+                    .color(egui::Color32::GRAY)
+                ));
+            } else if time_stack.can_switch_branch() {
+                ui.add(egui::Label::new(egui::RichText::new("B: other timeline")
+                    .color(egui::Color32::GRAY)
+                ));
+            }
         });
 
         egui::containers::Window::new("score")
@@ -351,6 +503,21 @@ pub fn draw_in_game_panels(
                     .color(egui::Color32::GRAY)
                     .heading()
                 ));
+
+                if stats.falls > 0 {
+                    ui.add(egui::Label::new(
+                        egui::RichText::new(format!("falls {}", stats.falls))
+                            .color(egui::Color32::GRAY)
+                    ));
+                }
+
+                let (passed, total) = checkpoint::count(&world);
+                if total > 0 {
+                    ui.add(egui::Label::new(
+                        egui::RichText::new(format!("checkpoints {}/{}", passed, total))
+                            .color(egui::Color32::GRAY)
+                    ));
+                }
             });
 
     // actionable objects panel
@@ -11,6 +11,8 @@ pub struct Clock {
     elapsed: f32,
     current_rewind: f32,
     max_rewind: f32,
+    /// Start angles and lengths of the slow motion segments
+    slowed: Vec<(f32, f32)>,
     size: f32,
 }
 
@@ -20,9 +22,15 @@ impl Clock {
             elapsed: elapsed,
             current_rewind: current_rewind,
             max_rewind: max_rewind,
+            slowed: Vec::new(),
             size: 64.0,
         }
     }
+
+    pub fn slowed(mut self, slowed: Vec<(f32, f32)>) -> Self {
+        self.slowed = slowed;
+        self
+    }
 }
 
 impl Widget for Clock {
@@ -31,6 +39,7 @@ impl Widget for Clock {
             elapsed,
             current_rewind,
             max_rewind,
+            slowed,
             size,
         } = self;
 
@@ -79,6 +88,12 @@ impl Widget for Clock {
             }
         }
 
+        for (angle, length) in slowed {
+            angles.push(angle);
+            lengths.push(length);
+            colors.push(Color32::from_rgb(255,165,0));
+        }
+
         if current_rewind > 0.0 {
             angles.push(elapsed);
             lengths.push(-current_rewind);
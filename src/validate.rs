@@ -0,0 +1,158 @@
+use crate::headless;
+use crate::level::{ Level, Objects, };
+
+use std::collections::HashSet;
+use std::path;
+
+/// Checks a level file and returns every problem found in it
+pub fn validate(file_name: &str) -> Vec<String> {
+    let level = match Level::from_file(file_name) {
+        Ok(level) => level,
+        Err(e) => return vec![e.to_string()],
+    };
+
+    let mut problems = Vec::new();
+
+    let players = level.objects().iter()
+        .filter(|object| matches!(object, Objects::Player(_)))
+        .count();
+
+    if players != 1 {
+        problems.push(format!(
+            "{}: exactly one Player object is required, found {}",
+            file_name, players
+        ));
+    }
+
+    let mut ids = HashSet::new();
+
+    for id in level.objects().iter().filter_map(|object| object.id()) {
+        if !ids.insert(id) {
+            problems.push(format!("{}: duplicate object id {}", file_name, id));
+        }
+    }
+
+    for object in level.objects().iter() {
+        if let Objects::Switch(init_state) = object {
+            for target in init_state.targets.iter() {
+                if !ids.contains(target.as_str()) {
+                    problems.push(format!(
+                        "{}: switch target {} does not exist", file_name, target
+                    ));
+                }
+            }
+        }
+    }
+
+    let model_path = ["assets/levels/", level.model(), ".gltf"].join("");
+
+    if !path::Path::new(&model_path).exists() {
+        problems.push(format!("{}: model {} does not exist", file_name, model_path));
+        return problems;
+    }
+
+    let vertices = match headless::read_mesh(&model_path) {
+        Ok((vertices, _)) => vertices,
+        Err(e) => {
+            problems.push(format!("{}: {}", file_name, e));
+            return problems;
+        },
+    };
+
+    let (min, max) = bounding_box(&vertices);
+
+    for object in level.objects().iter() {
+        if let Objects::MovingPlatform(init_state) = object {
+            let platform_path = ["assets/levels/", &init_state.model, ".gltf"].join("");
+
+            if let Err(e) = headless::read_mesh(&platform_path) {
+                problems.push(format!("{}: moving platform {}", file_name, e));
+            }
+
+            if init_state.waypoints.is_empty() {
+                problems.push(format!("{}: moving platform without waypoints", file_name));
+            }
+        }
+
+        if let Objects::TimeCapsule(init_state) = object {
+            if let Some(model) = init_state.model.as_ref() {
+                let capsule_path = ["assets/", model, ".gltf"].join("");
+
+                if !path::Path::new(&capsule_path).exists() {
+                    problems.push(format!(
+                        "{}: time capsule model {} does not exist", file_name, capsule_path
+                    ));
+                }
+            }
+        }
+
+        let (what, model) = match object {
+            Objects::Door(init_state) => ("door", &init_state.model),
+            Objects::Crate(init_state) => ("crate", &init_state.model),
+            _ => continue,
+        };
+
+        if let Err(e) = headless::read_mesh(&["assets/levels/", model, ".gltf"].join("")) {
+            problems.push(format!("{}: {} {}", file_name, what, e));
+        }
+    }
+
+    let mut check_position = |what: &str, position: (f32, f32, f32)| {
+        let inside =
+            position.0 >= min[0] && position.0 <= max[0] &&
+            position.1 >= min[1] && position.1 <= max[1] &&
+            position.2 >= min[2] && position.2 <= max[2];
+
+        if !inside {
+            problems.push(format!(
+                "{}: {} {:?} is outside of the model bounding box {:?} - {:?}",
+                file_name, what, position, min, max
+            ));
+        }
+    };
+
+    check_position("target", level.target_position);
+
+    for object in level.objects().iter() {
+        match object {
+            Objects::Player(init_state) => check_position("player", init_state.position),
+            Objects::TimeCapsule(init_state) => check_position("time capsule", init_state.position),
+            Objects::Trampoline(init_state) => {
+                check_position("trampoline", init_state.base_position);
+                for button in init_state.button_position.iter().chain(init_state.buttons.iter()) {
+                    check_position("trampoline button", *button);
+                }
+            },
+            Objects::Checkpoint(init_state) => check_position("checkpoint", init_state.position),
+            Objects::MovingPlatform(init_state) => {
+                for waypoint in init_state.waypoints.iter() {
+                    check_position("platform waypoint", *waypoint);
+                }
+            },
+            Objects::Switch(init_state) => check_position("switch", init_state.position),
+            Objects::Crate(init_state) => check_position("crate", init_state.position),
+            Objects::Door(init_state) => {
+                check_position("closed door", init_state.closed.position);
+                check_position("open door", init_state.open.position);
+            },
+            // lights may shine on the level from outside of it
+            Objects::Light(_) => {},
+        }
+    }
+
+    problems
+}
+
+fn bounding_box(vertices: &[[f32; 3]]) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+
+    for vertex in vertices.iter() {
+        for i in 0..3 {
+            min[i] = min[i].min(vertex[i]);
+            max[i] = max[i].max(vertex[i]);
+        }
+    }
+
+    (min, max)
+}
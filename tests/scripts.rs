@@ -0,0 +1,24 @@
+use retime::headless::Script;
+
+use std::fs;
+
+/// Runs every `scripts/test_*.yaml` headless and checks its expected result
+#[test]
+fn test_scripts_pass() {
+    let mut file_paths = fs::read_dir("scripts").expect("scripts folder should exist")
+        .filter_map(|file| file.ok())
+        .filter_map(|file| file.file_name().to_str().map(|name| name.to_string()))
+        .filter(|name| name.starts_with("test_") && name.ends_with(".yaml"))
+        .map(|name| ["scripts/", &name].join(""))
+        .collect::<Vec<_>>();
+
+    file_paths.sort();
+
+    assert!(!file_paths.is_empty(), "no test scripts found");
+
+    let failures = file_paths.iter()
+        .filter_map(|file_path| Script::from_file(file_path).and_then(|script| script.run()).err())
+        .collect::<Vec<_>>();
+
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}